@@ -0,0 +1,195 @@
+//! The `pa_mainloop_api` vtable shared by the mainloop implementations of this crate.
+//!
+//! The `extern "C"` functions of the vtable check their arguments, keep panics from
+//! unwinding into libpulse and forward to a [`MainLoopBackend`], whose address is stored
+//! in the `userdata` of the vtable.
+//!
+//! Backends run the callbacks of their events through [`dispatch`], so that all
+//! backends are instrumented the same way.
+use libc;
+use libpulse_sys::*;
+use std::ptr::{null, null_mut};
+
+use panic_guard;
+
+/// The kinds of mainloop events.
+#[derive(Debug, Clone, Copy)]
+pub enum EventKind {
+    Io = 0,
+    Time = 1,
+    Defer = 2,
+}
+
+/// Runs the libpulse callback `f` of an event of the given kind.
+pub fn dispatch<T, F>(kind: EventKind, f: F) -> T where F: FnOnce() -> T {
+    #[cfg(feature = "tracing")]
+    let _span = ::tracing::trace_span!("pa_dispatch", kind = ?kind).entered();
+    #[cfg(not(feature = "tracing"))]
+    let _ = kind;
+    f()
+}
+
+/// The event stores of a mainloop implementation.
+///
+/// The functions without `self` receive events created by the same backend.
+pub trait MainLoopBackend {
+    fn io_new(&self, fd: libc::c_int, events: pa_io_event_flags_t, cb: pa_io_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_io_event;
+    unsafe fn io_enable(e: *mut pa_io_event, events: pa_io_event_flags_t);
+    unsafe fn io_free(e: *mut pa_io_event);
+    unsafe fn io_set_destroy(e: *mut pa_io_event, cb: pa_io_event_destroy_cb_t);
+
+    fn time_new(&self, tv: Option<&libc::timeval>, cb: pa_time_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_time_event;
+    unsafe fn time_restart(e: *mut pa_time_event, tv: Option<&libc::timeval>);
+    unsafe fn time_free(e: *mut pa_time_event);
+    unsafe fn time_set_destroy(e: *mut pa_time_event, cb: pa_time_event_destroy_cb_t);
+
+    fn defer_new(&self, cb: pa_defer_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_defer_event;
+    unsafe fn defer_enable(e: *mut pa_defer_event, enabled: bool);
+    unsafe fn defer_free(e: *mut pa_defer_event);
+    unsafe fn defer_set_destroy(e: *mut pa_defer_event, cb: pa_defer_event_destroy_cb_t);
+
+    fn quit(&self, retval: libc::c_int);
+}
+
+/// Returns the vtable for `B`.
+///
+/// `userdata` must be set to the address of the backend before the vtable is used.
+pub fn vtable<B: MainLoopBackend>() -> pa_mainloop_api {
+    pa_mainloop_api {
+        userdata: null_mut(),
+        io_new: Some(io_new_cb::<B>),
+        io_enable: Some(io_enable_cb::<B>),
+        io_free: Some(io_free_cb::<B>),
+        io_set_destroy: Some(io_set_destroy_cb::<B>),
+        time_new: Some(time_new_cb::<B>),
+        time_restart: Some(time_restart_cb::<B>),
+        time_free: Some(time_free_cb::<B>),
+        time_set_destroy: Some(time_set_destroy_cb::<B>),
+        defer_new: Some(defer_new_cb::<B>),
+        defer_enable: Some(defer_enable_cb::<B>),
+        defer_free: Some(defer_free_cb::<B>),
+        defer_set_destroy: Some(defer_set_destroy_cb::<B>),
+        quit: Some(quit_cb::<B>),
+    }
+}
+
+unsafe fn run_api_function<B, T, F>(a: *mut pa_mainloop_api, f: F) -> T where B: MainLoopBackend, F: FnOnce(&B) -> T {
+    assert!(a != null_mut());
+    assert!((*a).userdata != null_mut());
+    f(&*((*a).userdata as *const B))
+}
+
+unsafe extern "C" fn io_new_cb<B: MainLoopBackend>(a: *mut pa_mainloop_api,
+                                                   fd: libc::c_int,
+                                                   events: pa_io_event_flags_t,
+                                                   cb: pa_io_event_cb_t,
+                                                   userdata: *mut libc::c_void) -> *mut pa_io_event {
+    panic_guard::guard(null_mut(), || {
+        assert!(cb.is_some());
+        assert!(fd >= 0);
+        run_api_function(a, |data: &B| data.io_new(fd, events, cb, userdata))
+    })
+}
+
+unsafe extern "C" fn io_enable_cb<B: MainLoopBackend>(e: *mut pa_io_event,
+                                                      events: pa_io_event_flags_t) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::io_enable(e, events)
+    })
+}
+
+unsafe extern "C" fn io_free_cb<B: MainLoopBackend>(e: *mut pa_io_event) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::io_free(e)
+    })
+}
+
+unsafe extern "C" fn io_set_destroy_cb<B: MainLoopBackend>(e: *mut pa_io_event,
+                                                           cb: pa_io_event_destroy_cb_t) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::io_set_destroy(e, cb)
+    })
+}
+
+unsafe extern "C" fn time_new_cb<B: MainLoopBackend>(a: *mut pa_mainloop_api,
+                                                     tv: *const libc::timeval,
+                                                     cb: pa_time_event_cb_t,
+                                                     userdata: *mut libc::c_void) -> *mut pa_time_event {
+    panic_guard::guard(null_mut(), || {
+        assert!(cb.is_some());
+        run_api_function(a, |data: &B| data.time_new(ref_from_ptr(tv), cb, userdata))
+    })
+}
+
+unsafe extern "C" fn time_restart_cb<B: MainLoopBackend>(e: *mut pa_time_event,
+                                                         tv: *const libc::timeval) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::time_restart(e, ref_from_ptr(tv))
+    })
+}
+
+unsafe extern "C" fn time_free_cb<B: MainLoopBackend>(e: *mut pa_time_event) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::time_free(e)
+    })
+}
+
+unsafe extern "C" fn time_set_destroy_cb<B: MainLoopBackend>(e: *mut pa_time_event,
+                                                             cb: pa_time_event_destroy_cb_t) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::time_set_destroy(e, cb)
+    })
+}
+
+unsafe extern "C" fn defer_new_cb<B: MainLoopBackend>(a: *mut pa_mainloop_api,
+                                                      cb: pa_defer_event_cb_t,
+                                                      userdata: *mut libc::c_void) -> *mut pa_defer_event {
+    panic_guard::guard(null_mut(), || {
+        assert!(cb.is_some());
+        run_api_function(a, |data: &B| data.defer_new(cb, userdata))
+    })
+}
+
+unsafe extern "C" fn defer_enable_cb<B: MainLoopBackend>(e: *mut pa_defer_event,
+                                                         b: libc::c_int) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::defer_enable(e, b != 0)
+    })
+}
+
+unsafe extern "C" fn defer_free_cb<B: MainLoopBackend>(e: *mut pa_defer_event) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::defer_free(e)
+    })
+}
+
+unsafe extern "C" fn defer_set_destroy_cb<B: MainLoopBackend>(e: *mut pa_defer_event,
+                                                              cb: pa_defer_event_destroy_cb_t) {
+    panic_guard::guard((), || {
+        assert!(e != null_mut());
+        B::defer_set_destroy(e, cb)
+    })
+}
+
+unsafe extern "C" fn quit_cb<B: MainLoopBackend>(a: *mut pa_mainloop_api,
+                                                 retval: libc::c_int) {
+    panic_guard::guard((), || {
+        run_api_function(a, |data: &B| data.quit(retval))
+    })
+}
+
+unsafe fn ref_from_ptr<'a, T>(ptr: *const T) -> Option<&'a T> {
+    if ptr == null() {
+        None
+    } else {
+        Some(&*ptr)
+    }
+}
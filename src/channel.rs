@@ -0,0 +1,208 @@
+//! Channels that can wake both `futures` 0.1 tasks and `std::task` wakers.
+//!
+//! The public futures and streams of this crate implement both the
+//! `futures` 0.1 and the `std::future` traits on top of these channels.
+//! All channels are single-threaded, except for the `sync_oneshot` channel.
+use futures::task::{self, Task};
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+/// The task to wake when a channel becomes ready.
+#[derive(Clone)]
+pub enum TaskWaker {
+    Legacy(Task),
+    Std(Waker),
+}
+
+impl TaskWaker {
+    /// Returns a waker for the current `futures` 0.1 task.
+    pub fn current() -> TaskWaker {
+        TaskWaker::Legacy(task::current())
+    }
+
+    /// Returns a waker for a `std::task` context.
+    pub fn from_context(cx: &Context) -> TaskWaker {
+        TaskWaker::Std(cx.waker().clone())
+    }
+
+    pub fn wake(self) {
+        match self {
+            TaskWaker::Legacy(task) => task.notify(),
+            TaskWaker::Std(waker) => waker.wake(),
+        }
+    }
+}
+
+/// Error returned from a oneshot receiver if the sender was dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Canceled;
+
+struct OneshotInner<T> {
+    value: Option<T>,
+    closed: bool,
+    waker: Option<TaskWaker>,
+}
+
+pub struct OneshotSender<T>(Rc<RefCell<OneshotInner<T>>>);
+
+pub struct OneshotReceiver<T>(Rc<RefCell<OneshotInner<T>>>);
+
+pub fn oneshot<T>() -> (OneshotSender<T>, OneshotReceiver<T>) {
+    let inner = Rc::new(RefCell::new(OneshotInner { value: None, closed: false, waker: None }));
+    (OneshotSender(inner.clone()), OneshotReceiver(inner))
+}
+
+impl<T> OneshotSender<T> {
+    pub fn send(self, value: T) {
+        self.0.borrow_mut().value = Some(value);
+    }
+}
+
+impl<T> Drop for OneshotSender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut inner = self.0.borrow_mut();
+            inner.closed = true;
+            inner.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> OneshotReceiver<T> {
+    pub fn poll(&mut self, waker: &TaskWaker) -> Poll<Result<T, Canceled>> {
+        let mut inner = self.0.borrow_mut();
+        if let Some(value) = inner.value.take() {
+            Poll::Ready(Ok(value))
+        } else if inner.closed {
+            Poll::Ready(Err(Canceled))
+        } else {
+            inner.waker = Some(waker.clone());
+            Poll::Pending
+        }
+    }
+}
+
+struct SyncOneshotInner<T> {
+    value: Option<T>,
+    closed: bool,
+    waker: Option<TaskWaker>,
+}
+
+/// Sending half of a oneshot channel that can be used across threads.
+pub struct SyncOneshotSender<T>(Arc<Mutex<SyncOneshotInner<T>>>);
+
+pub struct SyncOneshotReceiver<T>(Arc<Mutex<SyncOneshotInner<T>>>);
+
+pub fn sync_oneshot<T>() -> (SyncOneshotSender<T>, SyncOneshotReceiver<T>) {
+    let inner = Arc::new(Mutex::new(SyncOneshotInner { value: None, closed: false, waker: None }));
+    (SyncOneshotSender(inner.clone()), SyncOneshotReceiver(inner))
+}
+
+impl<T> SyncOneshotSender<T> {
+    pub fn send(self, value: T) {
+        self.0.lock().unwrap().value = Some(value);
+    }
+}
+
+impl<T> Drop for SyncOneshotSender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut inner = self.0.lock().unwrap();
+            inner.closed = true;
+            inner.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> SyncOneshotReceiver<T> {
+    pub fn poll(&mut self, waker: &TaskWaker) -> Poll<Result<T, Canceled>> {
+        let mut inner = self.0.lock().unwrap();
+        if let Some(value) = inner.value.take() {
+            Poll::Ready(Ok(value))
+        } else if inner.closed {
+            Poll::Ready(Err(Canceled))
+        } else {
+            inner.waker = Some(waker.clone());
+            Poll::Pending
+        }
+    }
+}
+
+struct BroadcastQueue<T> {
+    items: VecDeque<T>,
+    closed: bool,
+    waker: Option<TaskWaker>,
+}
+
+/// Sends every value to all receivers that exist at the time of sending.
+pub struct BroadcastSender<T>(RefCell<Vec<Weak<RefCell<BroadcastQueue<T>>>>>);
+
+pub struct BroadcastReceiver<T>(Rc<RefCell<BroadcastQueue<T>>>);
+
+impl<T: Clone> BroadcastSender<T> {
+    pub fn new() -> BroadcastSender<T> {
+        BroadcastSender(RefCell::new(Vec::new()))
+    }
+
+    pub fn subscribe(&self) -> BroadcastReceiver<T> {
+        let queue = Rc::new(RefCell::new(BroadcastQueue { items: VecDeque::new(), closed: false, waker: None }));
+        self.0.borrow_mut().push(Rc::downgrade(&queue));
+        BroadcastReceiver(queue)
+    }
+
+    pub fn send(&self, value: T) {
+        let receivers: Vec<_> = {
+            let mut receivers = self.0.borrow_mut();
+            receivers.retain(|r| r.upgrade().is_some());
+            receivers.iter().filter_map(|r| r.upgrade()).collect()
+        };
+        receivers.into_iter().for_each(|r| {
+            let waker = {
+                let mut queue = r.borrow_mut();
+                queue.items.push_back(value.clone());
+                queue.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+    }
+}
+
+impl<T> Drop for BroadcastSender<T> {
+    fn drop(&mut self) {
+        self.0.get_mut().drain(..).filter_map(|r| r.upgrade()).for_each(|r| {
+            let waker = {
+                let mut queue = r.borrow_mut();
+                queue.closed = true;
+                queue.waker.take()
+            };
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        });
+    }
+}
+
+impl<T> BroadcastReceiver<T> {
+    pub fn poll_next(&mut self, waker: &TaskWaker) -> Poll<Option<T>> {
+        let mut queue = self.0.borrow_mut();
+        if let Some(value) = queue.items.pop_front() {
+            Poll::Ready(Some(value))
+        } else if queue.closed {
+            Poll::Ready(None)
+        } else {
+            queue.waker = Some(waker.clone());
+            Poll::Pending
+        }
+    }
+}
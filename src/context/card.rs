@@ -0,0 +1,152 @@
+use libpulse_sys::*;
+use std::slice;
+
+use operation::FromRawInfo;
+use util::string_from_ptr;
+
+/// Availability of a card port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortAvailable {
+    /// It is unknown whether the port is available.
+    Unknown,
+    /// The port is not available, e.g. because nothing is plugged in.
+    No,
+    /// The port is available.
+    Yes,
+}
+
+impl PortAvailable {
+    fn new(a: pa_port_available_t) -> PortAvailable {
+        match a {
+            PA_PORT_AVAILABLE_NO => PortAvailable::No,
+            PA_PORT_AVAILABLE_YES => PortAvailable::Yes,
+            _ => PortAvailable::Unknown,
+        }
+    }
+}
+
+/// Direction of a card port.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PortDirection {
+    /// The port is used for playback.
+    Output,
+    /// The port is used for recording.
+    Input,
+}
+
+/// A profile of a [`CardInfo`](struct.CardInfo.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardProfile {
+    /// Name of the profile.
+    pub name: String,
+    /// Human-readable description.
+    pub description: String,
+    /// Number of sinks this profile would create.
+    pub n_sinks: u32,
+    /// Number of sources this profile would create.
+    pub n_sources: u32,
+    /// The higher this value is, the more useful this profile is as a default.
+    pub priority: u32,
+    /// Whether the profile can currently be activated.
+    pub available: bool,
+}
+
+impl CardProfile {
+    unsafe fn new(raw: &pa_card_profile_info2) -> CardProfile {
+        CardProfile {
+            name: string_from_ptr(raw.name).unwrap_or_default(),
+            description: string_from_ptr(raw.description).unwrap_or_default(),
+            n_sinks: raw.n_sinks,
+            n_sources: raw.n_sources,
+            priority: raw.priority,
+            available: raw.available != 0,
+        }
+    }
+}
+
+/// A port of a [`CardInfo`](struct.CardInfo.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardPort {
+    /// Name of the port.
+    pub name: String,
+    /// Human-readable description.
+    pub description: String,
+    /// The higher this value is, the more useful this port is as a default.
+    pub priority: u32,
+    /// Whether the port is available.
+    pub available: PortAvailable,
+    /// Whether this is an input or an output port.
+    pub direction: PortDirection,
+    /// Names of the profiles this port is part of.
+    pub profiles: Vec<String>,
+    /// Latency offset of the port in microseconds.
+    pub latency_offset: i64,
+}
+
+impl CardPort {
+    unsafe fn new(raw: &pa_card_port_info) -> CardPort {
+        CardPort {
+            name: string_from_ptr(raw.name).unwrap_or_default(),
+            description: string_from_ptr(raw.description).unwrap_or_default(),
+            priority: raw.priority,
+            available: PortAvailable::new(raw.available as pa_port_available_t),
+            direction: if raw.direction as pa_direction_t & PA_DIRECTION_INPUT != 0 {
+                PortDirection::Input
+            } else {
+                PortDirection::Output
+            },
+            profiles: pointer_array(raw.profiles2, raw.n_profiles).iter()
+                .map(|p| string_from_ptr((**p).name).unwrap_or_default())
+                .collect(),
+            latency_offset: raw.latency_offset,
+        }
+    }
+}
+
+/// Information about a sound card.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CardInfo {
+    /// Index of the card.
+    pub index: u32,
+    /// Name of the card.
+    pub name: String,
+    /// Driver name.
+    pub driver: String,
+    /// Available profiles.
+    pub profiles: Vec<CardProfile>,
+    /// Name of the active profile.
+    pub active_profile: Option<String>,
+    /// Ports of the card.
+    pub ports: Vec<CardPort>,
+}
+
+impl FromRawInfo for CardInfo {
+    type Raw = pa_card_info;
+
+    unsafe fn from_raw(raw: &pa_card_info) -> CardInfo {
+        CardInfo {
+            index: raw.index,
+            name: string_from_ptr(raw.name).unwrap_or_default(),
+            driver: string_from_ptr(raw.driver).unwrap_or_default(),
+            profiles: pointer_array(raw.profiles2, raw.n_profiles).iter()
+                .map(|p| CardProfile::new(&**p))
+                .collect(),
+            active_profile: if raw.active_profile2.is_null() {
+                None
+            } else {
+                string_from_ptr((*raw.active_profile2).name)
+            },
+            ports: pointer_array(raw.ports, raw.n_ports).iter()
+                .map(|p| CardPort::new(&**p))
+                .collect(),
+        }
+    }
+}
+
+unsafe fn pointer_array<'a, T>(ptr: *mut *mut T, len: u32) -> &'a [*mut T] {
+    if ptr.is_null() {
+        &[]
+    } else {
+        slice::from_raw_parts(ptr, len as usize)
+    }
+}
@@ -0,0 +1,72 @@
+use futures::{Async, Future, Poll};
+use libc;
+use libpulse_sys::*;
+use std::future::Future as StdFuture;
+use std::pin::Pin;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::TaskWaker;
+use error::PaError;
+use panic_guard;
+use refcount::RefCounted;
+use super::state::{PaContextState, PaContextStateStream};
+
+/// A future that resolves once the context connection is established.
+///
+/// The future fails if the context enters the `Failed` or `Terminated` state
+/// before it is ready. The connection attempt continues if the future is dropped.
+///
+/// This type implements both `std::future::Future` and `futures` 0.1 `Future`.
+#[must_use = "futures do nothing unless polled"]
+pub struct PaContextConnectFuture {
+    raw: RefCounted<pa_context>,
+    states: PaContextStateStream,
+    error: Option<PaError>,
+}
+
+impl PaContextConnectFuture {
+    pub(crate) fn new(raw: RefCounted<pa_context>, states: PaContextStateStream, error: Option<PaError>) -> PaContextConnectFuture {
+        PaContextConnectFuture { raw, states, error }
+    }
+
+    fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Result<(), PaError>> {
+        panic_guard::resume();
+        if let Some(e) = self.error.take() {
+            return StdPoll::Ready(Err(e));
+        }
+        loop {
+            match self.states.poll_impl(waker) {
+                StdPoll::Ready(Some(PaContextState::Ready)) => return StdPoll::Ready(Ok(())),
+                StdPoll::Ready(Some(PaContextState::Failed)) | StdPoll::Ready(Some(PaContextState::Terminated)) => {
+                    return StdPoll::Ready(Err(match unsafe { pa_context_errno(self.raw.get()) } {
+                        0 => PaError::new(PA_ERR_CONNECTIONTERMINATED as libc::c_int),
+                        e => PaError::new(e),
+                    }));
+                },
+                StdPoll::Ready(Some(_)) => (),
+                StdPoll::Ready(None) => return StdPoll::Ready(Err(PaError::new(PA_ERR_CONNECTIONTERMINATED as libc::c_int))),
+                StdPoll::Pending => return StdPoll::Pending,
+            }
+        }
+    }
+}
+
+impl Future for PaContextConnectFuture {
+    type Item = ();
+    type Error = PaError;
+
+    fn poll(&mut self) -> Poll<(), PaError> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(res) => res.map(Async::Ready),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl StdFuture for PaContextConnectFuture {
+    type Output = Result<(), PaError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Result<(), PaError>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
@@ -0,0 +1,349 @@
+use futures::{Async, Future, Poll};
+use libc;
+use libpulse_sys::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future as StdFuture;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll as StdPoll, Wake, Waker};
+
+use channel::{self, Canceled, SyncOneshotReceiver, SyncOneshotSender, TaskWaker};
+use error::PaError;
+use mainloop_api::PaMainLoopApi;
+use panic_guard;
+use super::{CardInfo, PaContext, PaContextState, SampleInfo};
+
+type LocalFuture = Pin<Box<StdFuture<Output = ()>>>;
+type Command<M> = Box<FnOnce(&PaContext<M>) -> LocalFuture + Send>;
+
+// The write end of the pipe that wakes up the dispatcher.
+struct Wakeup(RawFd);
+
+impl Wakeup {
+    fn wake(&self) {
+        // If the pipe is full, a wakeup is pending anyway.
+        unsafe { libc::write(self.0, b"\0".as_ptr() as *const libc::c_void, 1) };
+    }
+}
+
+impl Drop for Wakeup {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0) };
+    }
+}
+
+struct Shared<M> {
+    queue: Mutex<Queue<M>>,
+    handles: AtomicUsize,
+    wakeup: Arc<Wakeup>,
+}
+
+struct Queue<M> {
+    commands: VecDeque<Command<M>>,
+    closed: bool,
+}
+
+impl<M> Shared<M> {
+    fn push(&self, command: Command<M>) {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.closed {
+                // Dropping the command cancels its future.
+                return;
+            }
+            queue.commands.push_back(command);
+        }
+        self.wakeup.wake();
+    }
+}
+
+/// A handle for controlling a [`PaContext`](struct.PaContext.html) from other threads.
+///
+/// The handle is `Send` and `Sync`. Commands are run on the thread that owns the
+/// context, from within its mainloop, and their results are returned through
+/// [`ContextFuture`](struct.ContextFuture.html)s that can be awaited on any thread.
+///
+/// Any operation of the context can be sent with [`run`](#method.run) or
+/// [`spawn`](#method.spawn), e.g. volume changes or creating and driving a stream,
+/// since objects like [`PaStream`](../stream/struct.PaStream.html) cannot leave the
+/// thread that owns the context. Only a few queries have convenience methods.
+///
+/// The handles keep the context and its mainloop alive, until all handles are
+/// dropped and all spawned futures have completed. Commands are only run while the
+/// mainloop is running.
+pub struct ContextHandle<M> {
+    shared: Arc<Shared<M>>,
+}
+
+fn pipe() -> io::Result<[RawFd; 2]> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    for &fd in fds.iter() {
+        let ok = unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            flags >= 0 && libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) >= 0 && libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC) >= 0
+        };
+        if !ok {
+            let err = io::Error::last_os_error();
+            unsafe {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+            }
+            return Err(err);
+        }
+    }
+    Ok(fds)
+}
+
+pub fn new<M: PaMainLoopApi + 'static>(context: &PaContext<M>) -> io::Result<ContextHandle<M>> {
+    let fds = pipe()?;
+    let shared = Arc::new(Shared {
+        queue: Mutex::new(Queue { commands: VecDeque::new(), closed: false }),
+        handles: AtomicUsize::new(1),
+        wakeup: Arc::new(Wakeup(fds[1])),
+    });
+    let dispatcher = Box::new(Dispatcher {
+        context: context.clone(),
+        shared: shared.clone(),
+        read_fd: fds[0],
+        tasks: RefCell::new(Vec::new()),
+    });
+    let api = context.mainloop_api.get_api();
+    let userdata = Box::into_raw(dispatcher);
+    unsafe {
+        let event = (*api).io_new.unwrap()(api, fds[0], PA_IO_EVENT_INPUT, Some(io_cb::<M>), userdata as *mut libc::c_void);
+        assert!(event != null_mut());
+        (*api).io_set_destroy.unwrap()(event, Some(io_destroy_cb::<M>));
+    }
+    Ok(ContextHandle { shared })
+}
+
+impl<M: PaMainLoopApi + 'static> ContextHandle<M> {
+    /// Runs `f` with the context on the thread that owns it.
+    ///
+    /// The returned future resolves to the return value of `f`.
+    pub fn run<F, T>(&self, f: F) -> ContextFuture<T>
+        where F: FnOnce(&PaContext<M>) -> T + Send + 'static,
+              T: Send + 'static {
+        self.spawn(move |ctx| ::std::future::ready(f(ctx)))
+    }
+
+    /// Runs `f` with the context on the thread that owns it, and drives the future it
+    /// returns on that thread.
+    ///
+    /// The returned future resolves to the output of the future returned by `f`. This
+    /// allows using futures of this crate, which are not `Send`, from other threads.
+    pub fn spawn<F, Fut>(&self, f: F) -> ContextFuture<Fut::Output>
+        where F: FnOnce(&PaContext<M>) -> Fut + Send + 'static,
+              Fut: StdFuture + 'static,
+              Fut::Output: Send + 'static {
+        let (sender, receiver) = channel::sync_oneshot();
+        self.shared.push(Box::new(move |ctx: &PaContext<M>| -> LocalFuture {
+            Box::pin(SendOutput { future: Box::pin(f(ctx)), sender: Some(sender) })
+        }));
+        ContextFuture(receiver)
+    }
+
+    /// Returns the current state of the context.
+    pub fn get_state(&self) -> ContextFuture<PaContextState> {
+        self.run(|ctx| ctx.get_state())
+    }
+
+    /// Returns information about all cards.
+    /// See [`PaContext::get_card_info_list`](struct.PaContext.html#method.get_card_info_list).
+    pub fn get_card_info_list(&self) -> ContextFuture<Result<Vec<CardInfo>, PaError>> {
+        self.spawn(|ctx| ctx.get_card_info_list())
+    }
+
+    /// Returns information about all samples in the sample cache.
+    /// See [`PaContext::get_sample_info_list`](struct.PaContext.html#method.get_sample_info_list).
+    pub fn get_sample_info_list(&self) -> ContextFuture<Result<Vec<SampleInfo>, PaError>> {
+        self.spawn(|ctx| ctx.get_sample_info_list())
+    }
+}
+
+impl<M> Clone for ContextHandle<M> {
+    fn clone(&self) -> ContextHandle<M> {
+        self.shared.handles.fetch_add(1, Ordering::Relaxed);
+        ContextHandle { shared: self.shared.clone() }
+    }
+}
+
+impl<M> Drop for ContextHandle<M> {
+    fn drop(&mut self) {
+        if self.shared.handles.fetch_sub(1, Ordering::AcqRel) == 1 {
+            // Let the dispatcher shut down.
+            self.shared.wakeup.wake();
+        }
+    }
+}
+
+/// A future for the result of a command sent through a [`ContextHandle`].
+///
+/// Fails with `PA_ERR_CONNECTIONTERMINATED` if the command was dropped without
+/// completing, e.g. because it panicked.
+///
+/// This type is `Send` if the result is, and implements both `std::future::Future`
+/// and `futures` 0.1 `Future`.
+#[must_use]
+pub struct ContextFuture<T>(SyncOneshotReceiver<T>);
+
+impl<T> ContextFuture<T> {
+    fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Result<T, PaError>> {
+        panic_guard::resume();
+        match self.0.poll(waker) {
+            StdPoll::Ready(Ok(value)) => StdPoll::Ready(Ok(value)),
+            StdPoll::Ready(Err(Canceled)) => StdPoll::Ready(Err(PaError::new(PA_ERR_CONNECTIONTERMINATED as libc::c_int))),
+            StdPoll::Pending => StdPoll::Pending,
+        }
+    }
+}
+
+impl<T> Future for ContextFuture<T> {
+    type Item = T;
+    type Error = PaError;
+
+    fn poll(&mut self) -> Poll<T, PaError> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(res) => res.map(Async::Ready),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<T> StdFuture for ContextFuture<T> {
+    type Output = Result<T, PaError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Result<T, PaError>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
+
+impl<T> Unpin for ContextFuture<T> {}
+
+// Drives a future on the owner thread and sends its output to the handle.
+struct SendOutput<Fut: StdFuture> {
+    future: Pin<Box<Fut>>,
+    sender: Option<SyncOneshotSender<Fut::Output>>,
+}
+
+impl<Fut: StdFuture> StdFuture for SendOutput<Fut> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<()> {
+        let this = self.get_mut();
+        match this.future.as_mut().poll(cx) {
+            StdPoll::Ready(value) => {
+                if let Some(sender) = this.sender.take() {
+                    sender.send(value);
+                }
+                StdPoll::Ready(())
+            },
+            StdPoll::Pending => StdPoll::Pending,
+        }
+    }
+}
+
+impl<Fut: StdFuture> Unpin for SendOutput<Fut> {}
+
+struct TaskWake {
+    wakeup: Arc<Wakeup>,
+    woken: AtomicBool,
+}
+
+impl Wake for TaskWake {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.woken.store(true, Ordering::Release);
+        self.wakeup.wake();
+    }
+}
+
+struct Task {
+    future: LocalFuture,
+    wake: Arc<TaskWake>,
+}
+
+// Runs the commands on the owner thread. Owned by the io event of the read end of the pipe.
+struct Dispatcher<M: PaMainLoopApi> {
+    context: PaContext<M>,
+    shared: Arc<Shared<M>>,
+    read_fd: RawFd,
+    tasks: RefCell<Vec<Task>>,
+}
+
+impl<M: PaMainLoopApi> Dispatcher<M> {
+    /// Runs queued commands and woken tasks. Returns true when the dispatcher is no longer needed.
+    fn dispatch(&self) -> bool {
+        self.drain_pipe();
+
+        let commands = mem::replace(&mut self.shared.queue.lock().unwrap().commands, VecDeque::new());
+        let mut tasks = mem::replace(&mut *self.tasks.borrow_mut(), Vec::new());
+        // A panicking command or task is dropped, which cancels only its own future.
+        tasks.extend(commands.into_iter().filter_map(|command| {
+            panic_guard::guard(None, || Some(command(&self.context))).map(|future| Task {
+                future,
+                wake: Arc::new(TaskWake { wakeup: self.shared.wakeup.clone(), woken: AtomicBool::new(true) }),
+            })
+        }));
+        let tasks: Vec<Task> = tasks.into_iter().filter_map(|mut task| {
+            if task.wake.woken.swap(false, Ordering::AcqRel) {
+                let waker = Waker::from(task.wake.clone());
+                let mut cx = Context::from_waker(&waker);
+                if panic_guard::guard(true, || task.future.as_mut().poll(&mut cx).is_ready()) {
+                    return None;
+                }
+            }
+            Some(task)
+        }).collect();
+        let idle = tasks.is_empty();
+        *self.tasks.borrow_mut() = tasks;
+
+        idle && self.shared.handles.load(Ordering::Acquire) == 0
+    }
+
+    fn drain_pipe(&self) {
+        let mut buf = [0u8; 64];
+        while unsafe { libc::read(self.read_fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } > 0 {}
+    }
+}
+
+impl<M: PaMainLoopApi> Drop for Dispatcher<M> {
+    fn drop(&mut self) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.closed = true;
+            queue.commands.clear();
+        }
+        unsafe { libc::close(self.read_fd) };
+    }
+}
+
+extern "C" fn io_cb<M: PaMainLoopApi>(a: *mut pa_mainloop_api, e: *mut pa_io_event, _fd: libc::c_int, _events: pa_io_event_flags_t, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        assert!(userdata != null_mut());
+        let finished = unsafe { &*(userdata as *const Dispatcher<M>) }.dispatch();
+        if finished {
+            // The destroy callback frees the dispatcher.
+            unsafe { (*a).io_free.unwrap()(e) };
+        }
+    })
+}
+
+extern "C" fn io_destroy_cb<M: PaMainLoopApi>(_a: *mut pa_mainloop_api, _e: *mut pa_io_event, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        assert!(userdata != null_mut());
+        drop(unsafe { Box::from_raw(userdata as *mut Dispatcher<M>) });
+    })
+}
@@ -0,0 +1,286 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+#[cfg(feature = "pa_v15")]
+pub use self::sys::{pa_context_send_message_to_object, string_cb};
+
+// The message API was added in pulseaudio 15.
+#[cfg(feature = "pa_v15")]
+mod sys {
+    use libc;
+    use libpulse_sys::{pa_context, pa_context_errno, pa_operation};
+    use std::ffi::CStr;
+
+    use error::PaError;
+    use operation;
+    use panic_guard;
+
+    #[allow(non_camel_case_types)]
+    type pa_context_string_cb_t = Option<extern "C" fn(c: *mut pa_context, success: libc::c_int, response: *mut libc::c_char, userdata: *mut libc::c_void)>;
+
+    #[link(name = "pulse")]
+    extern "C" {
+        pub fn pa_context_send_message_to_object(c: *mut pa_context, recipient_name: *const libc::c_char, message: *const libc::c_char,
+                                                 message_parameters: *const libc::c_char, cb: pa_context_string_cb_t,
+                                                 userdata: *mut libc::c_void) -> *mut pa_operation;
+    }
+
+    /// Completion callback for operations that return a string.
+    pub extern "C" fn string_cb(ctx: *mut pa_context, success: libc::c_int, response: *mut libc::c_char, userdata: *mut libc::c_void) {
+        panic_guard::guard((), || {
+            let result = if success != 0 {
+                let response = if response.is_null() {
+                    String::new()
+                } else {
+                    unsafe { CStr::from_ptr(response) }.to_string_lossy().into_owned()
+                };
+                Ok(response)
+            } else {
+                Err(PaError::new(unsafe { pa_context_errno(ctx) }))
+            };
+            unsafe { operation::complete(userdata, result) };
+        })
+    }
+}
+
+/// A value in the parameter format of the pulseaudio message API.
+///
+/// Parameters and responses are sequences of elements enclosed in curly braces. An
+/// element is either a string, in which `{`, `}` and `\` are escaped with a backslash,
+/// or a list of elements. Numbers and booleans are sent as strings.
+///
+/// The `Display` implementation writes the value in the message parameter format.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MessageParam {
+    /// A string value.
+    String(String),
+    /// A list of values.
+    List(Vec<MessageParam>),
+}
+
+impl MessageParam {
+    /// Returns the string value, or `None` if this is a list.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            MessageParam::String(ref s) => Some(s),
+            MessageParam::List(_) => None,
+        }
+    }
+
+    /// Returns the list elements, or `None` if this is a string.
+    pub fn as_list(&self) -> Option<&[MessageParam]> {
+        match *self {
+            MessageParam::String(_) => None,
+            MessageParam::List(ref l) => Some(l),
+        }
+    }
+
+    /// Parses the string value as a number.
+    ///
+    /// Returns `None` if this is a list or the string is not a valid number.
+    pub fn parse<T: FromStr>(&self) -> Option<T> {
+        self.as_str().and_then(|s| s.trim().parse().ok())
+    }
+
+    /// Returns the boolean value of the string, as accepted by pulseaudio.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.as_str().map(|s| s.trim().to_lowercase()) {
+            Some(ref s) if s == "1" || s == "y" || s == "t" || s == "yes" || s == "true" || s == "on" => Some(true),
+            Some(ref s) if s == "0" || s == "n" || s == "f" || s == "no" || s == "false" || s == "off" => Some(false),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MessageParam {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("{")?;
+        match *self {
+            MessageParam::String(ref s) => {
+                for c in s.chars() {
+                    if c == '{' || c == '}' || c == '\\' {
+                        f.write_str("\\")?;
+                    }
+                    write!(f, "{}", c)?;
+                }
+            },
+            MessageParam::List(ref l) => {
+                for param in l {
+                    write!(f, "{}", param)?;
+                }
+            },
+        }
+        f.write_str("}")
+    }
+}
+
+/// Error returned when parsing malformed message parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MessageParamsError {
+    position: usize,
+}
+
+impl MessageParamsError {
+    /// Returns the byte offset of the error in the parsed string.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+impl fmt::Display for MessageParamsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Malformed message parameters at position {}", self.position)
+    }
+}
+
+impl Error for MessageParamsError {}
+
+/// Parses a message parameter string, like the response of
+/// [`PaContext::send_message_to_object`](struct.PaContext.html#method.send_message_to_object).
+///
+/// Whitespace between elements is ignored.
+pub fn parse_message_params(params: &str) -> Result<Vec<MessageParam>, MessageParamsError> {
+    let mut parser = Parser { input: params.as_bytes(), pos: 0 };
+    let elements = parser.elements()?;
+    if parser.pos < parser.input.len() {
+        return Err(parser.error());
+    }
+    Ok(elements)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self) -> MessageParamsError {
+        MessageParamsError { position: self.pos }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).cloned()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, |c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    // Parses elements until the end of the input or a closing brace.
+    fn elements(&mut self) -> Result<Vec<MessageParam>, MessageParamsError> {
+        let mut elements = Vec::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b'{') => {
+                    self.pos += 1;
+                    elements.push(self.element()?);
+                },
+                None | Some(b'}') => return Ok(elements),
+                Some(_) => return Err(self.error()),
+            }
+        }
+    }
+
+    // Parses the contents of an element after its opening brace, including the closing brace.
+    fn element(&mut self) -> Result<MessageParam, MessageParamsError> {
+        let start = self.pos;
+        self.skip_whitespace();
+        if self.peek() == Some(b'{') {
+            let list = self.elements()?;
+            return self.close().map(|_| MessageParam::List(list));
+        }
+        self.pos = start;
+        let mut value = Vec::new();
+        loop {
+            match self.peek() {
+                Some(b'\\') => {
+                    self.pos += 1;
+                    match self.peek() {
+                        Some(c) => value.push(c),
+                        None => return Err(self.error()),
+                    }
+                },
+                Some(b'{') | None => return Err(self.error()),
+                Some(b'}') => break,
+                Some(c) => value.push(c),
+            }
+            self.pos += 1;
+        }
+        self.close()?;
+        // The input is valid UTF-8 and only ASCII characters were removed.
+        Ok(MessageParam::String(String::from_utf8(value).unwrap()))
+    }
+
+    fn close(&mut self) -> Result<(), MessageParamsError> {
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::MessageParam::{List, String as Str};
+
+    fn s(value: &str) -> MessageParam {
+        Str(value.to_string())
+    }
+
+    #[test]
+    fn round_trip() {
+        let params = vec![s("plain"), List(vec![s("a"), List(vec![s("1"), s("")])]), s("x{y}\\z")];
+        let text: String = params.iter().map(|p| p.to_string()).collect();
+        assert_eq!(parse_message_params(&text), Ok(params));
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(s("a{b}c\\d").to_string(), "{a\\{b\\}c\\\\d}");
+        assert_eq!(parse_message_params("{a\\{b\\}c\\\\d}"), Ok(vec![s("a{b}c\\d")]));
+    }
+
+    #[test]
+    fn nested_lists() {
+        let parsed = parse_message_params("{{{aac}{AAC}}{{sbc}{SBC}}}").unwrap();
+        assert_eq!(parsed, vec![List(vec![List(vec![s("aac"), s("AAC")]), List(vec![s("sbc"), s("SBC")])])]);
+        let codecs = parsed[0].as_list().unwrap();
+        assert_eq!(codecs[1].as_list().unwrap()[0].as_str(), Some("sbc"));
+    }
+
+    #[test]
+    fn whitespace() {
+        assert_eq!(parse_message_params(" { {x} { y } }\n{ 42 } "), Ok(vec![List(vec![s("x"), s(" y ")]), s(" 42 ")]));
+        assert_eq!(parse_message_params("{ 42 }").unwrap()[0].parse::<u32>(), Some(42));
+        assert_eq!(parse_message_params("").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn unterminated() {
+        assert_eq!(parse_message_params("{abc").unwrap_err().position(), 4);
+        assert_eq!(parse_message_params("{{a}").unwrap_err().position(), 4);
+        assert_eq!(parse_message_params("{a\\").unwrap_err().position(), 3);
+        assert_eq!(parse_message_params("{ab{c}}").unwrap_err().position(), 3);
+    }
+
+    #[test]
+    fn trailing_garbage() {
+        assert_eq!(parse_message_params("{a}x").unwrap_err().position(), 3);
+        assert_eq!(parse_message_params("{a}}").unwrap_err().position(), 3);
+    }
+
+    #[test]
+    fn booleans() {
+        assert_eq!(s("yes").as_bool(), Some(true));
+        assert_eq!(s("0").as_bool(), Some(false));
+        assert_eq!(s("maybe").as_bool(), None);
+        assert_eq!(List(vec![]).as_bool(), None);
+    }
+}
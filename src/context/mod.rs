@@ -1,16 +1,36 @@
 //! Pulseaudio context.
+mod card;
+mod connect;
+mod handle;
+mod message;
+mod module;
+mod sample_cache;
 mod state;
 
 use libc;
 use libpulse_sys::*;
 use std::ffi::CStr;
+use std::io;
 use std::ptr::{null, null_mut};
 
+use error::PaError;
+use format::FormatInfo;
 use mainloop_api::PaMainLoopApi;
+use proplist::Proplist;
 use refcount::RefCounted;
+use sample::SampleSpec;
+use stream::PaSampleUploadFuture;
+use util::{index_to_option, string_from_ptr};
 
+pub use self::card::{CardInfo, CardPort, CardProfile, PortAvailable, PortDirection};
+pub use self::connect::PaContextConnectFuture;
+pub use self::handle::{ContextFuture, ContextHandle};
+pub use self::message::{parse_message_params, MessageParam, MessageParamsError};
+pub use self::module::ModuleArgs;
+pub use self::sample_cache::SampleInfo;
 pub use self::state::PaContextState;
 pub use self::state::PaContextStateStream;
+pub use operation::PaOperationFuture;
 use ::explicit_cleanup::ExplicitCleanup;
 
 /// The basic object for a connection to a pulseaudio server.
@@ -46,6 +66,29 @@ impl<M: PaMainLoopApi> PaContext<M> {
         }
     }
 
+    /// Returns the reference to the underlying `pa_context`.
+    ///
+    /// This allows calling libpulse functions that are not wrapped by this crate, e.g.
+    /// with [`PaOperationFuture::start_success`](struct.PaOperationFuture.html#method.start_success).
+    /// The pointer must only be used on the thread that runs the mainloop of this context.
+    pub fn raw(&self) -> &RefCounted<pa_context> {
+        &self.raw
+    }
+
+    /// Returns the raw pointer to the underlying `pa_context`.
+    ///
+    /// No reference is transferred to the caller, the pointer is valid as long as this
+    /// context is alive. See [`raw`](#method.raw).
+    ///
+    /// # Safety
+    ///
+    /// The pointer must only be used on the thread that runs the mainloop of this
+    /// context, and must not be used to change the callbacks that this crate sets, like
+    /// the state callback.
+    pub unsafe fn as_raw(&self) -> *mut pa_context {
+        self.raw.get()
+    }
+
     /// Returns the error number of the last failed operation.
     ///
     /// This number can be converted into a human-readable string using the
@@ -67,16 +110,215 @@ impl<M: PaMainLoopApi> PaContext<M> {
 
     /// Connect the context to the specified server.
     ///
-    /// If server is None, connect to the default server. This routine may but will not always return synchronously on error.
-    /// Use the stream returned by [`get_state_stream`](#method.get_state_stream) to be notified when the connection is established.
-    pub fn connect(&self, server: Option<&CStr>) -> bool {
-        unsafe { pa_context_connect(self.raw.get(), match server { Some(s) => s.as_ptr(), None => null() }, PA_CONTEXT_NOAUTOSPAWN, null())  >= 0 }
+    /// If server is None, connect to the default server. The returned future resolves when the
+    /// connection is established, or fails if the connection cannot be established.
+    /// Use the stream returned by [`get_state_stream`](#method.get_state_stream) to be notified of
+    /// further state changes.
+    pub fn connect(&self, server: Option<&CStr>) -> PaContextConnectFuture {
+        let states = self.get_state_stream();
+        let error = if unsafe { pa_context_connect(self.raw.get(), match server { Some(s) => s.as_ptr(), None => null() }, PA_CONTEXT_NOAUTOSPAWN, null()) } < 0 {
+            Some(PaError::new(self.errno()))
+        } else {
+            None
+        };
+        PaContextConnectFuture::new((*self.raw).clone(), states, error)
     }
 
     /// Terminate the context connection immediately.
+    ///
+    /// To shut down gracefully, wait for [`drain`](#method.drain) to complete first.
     pub fn disconnect(&self) {
         unsafe { pa_context_disconnect(self.raw.get()) }
     }
+
+    /// Returns a future that completes when all outstanding operations have finished.
+    ///
+    /// If no operations are pending, the future completes immediately.
+    pub fn drain(&self) -> PaOperationFuture<()> {
+        if !self.is_pending() {
+            return PaOperationFuture::ready(Ok(()));
+        }
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_notify(raw, |cb, userdata| pa_context_drain(raw, Some(cb), userdata)) }
+    }
+
+    /// Tells the daemon to exit.
+    ///
+    /// The returned future will probably never complete successfully, since the daemon
+    /// exits before it can reply.
+    pub fn exit_daemon(&self) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_context_exit_daemon(raw, Some(cb), userdata)) }
+    }
+
+    /// Sets the application name on the server.
+    pub fn set_name(&self, name: &CStr) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_context_set_name(raw, name.as_ptr(), Some(cb), userdata)) }
+    }
+
+    /// Loads a module on the server.
+    ///
+    /// The future resolves to the index of the loaded module, which can be passed
+    /// to [`unload_module`](#method.unload_module).
+    pub fn load_module(&self, name: &CStr, args: &ModuleArgs) -> PaOperationFuture<u32> {
+        let raw = self.raw.get();
+        let args = args.to_cstring();
+        unsafe { PaOperationFuture::start_index(raw, |cb, userdata| pa_context_load_module(raw, name.as_ptr(), args.as_ptr(), Some(cb), userdata)) }
+    }
+
+    /// Unloads the module with the specified index from the server.
+    pub fn unload_module(&self, index: u32) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_context_unload_module(raw, index, Some(cb), userdata)) }
+    }
+
+    /// Sets the formats supported by the sink with the specified index.
+    ///
+    /// This is used to enable passthrough of compressed formats to devices that
+    /// support them, e.g. HDMI receivers. Requires `module-device-restore`, which
+    /// stores the formats. Fails with `PA_ERR_INVALID` if more than 255 formats are given.
+    pub fn set_sink_formats(&self, index: u32, formats: &[FormatInfo]) -> PaOperationFuture<()> {
+        if formats.len() > u8::max_value() as usize {
+            return PaOperationFuture::ready(Err(PaError::new(PA_ERR_INVALID as libc::c_int)));
+        }
+        let raw = self.raw.get();
+        let mut raw_formats: Vec<*mut pa_format_info> = formats.iter().map(|f| f.as_raw()).collect();
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_ext_device_restore_save_formats(raw, PA_DEVICE_TYPE_SINK, index, raw_formats.len() as u8, raw_formats.as_mut_ptr(), Some(cb), userdata)) }
+    }
+
+    /// Returns information about all sound cards.
+    pub fn get_card_info_list(&self) -> PaOperationFuture<Vec<CardInfo>> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_list(raw, |cb, userdata| pa_context_get_card_info_list(raw, Some(cb), userdata)) }
+    }
+
+    /// Changes the profile of the card with the specified index.
+    pub fn set_card_profile_by_index(&self, index: u32, profile: &CStr) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_context_set_card_profile_by_index(raw, index, profile.as_ptr(), Some(cb), userdata)) }
+    }
+
+    /// Changes the profile of the card with the specified name.
+    pub fn set_card_profile_by_name(&self, name: &CStr, profile: &CStr) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_context_set_card_profile_by_name(raw, name.as_ptr(), profile.as_ptr(), Some(cb), userdata)) }
+    }
+
+    /// Sets the latency offset of a card port, in microseconds.
+    pub fn set_port_latency_offset(&self, card_name: &CStr, port_name: &CStr, offset: i64) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_context_set_port_latency_offset(raw, card_name.as_ptr(), port_name.as_ptr(), offset, Some(cb), userdata)) }
+    }
+
+    /// Uploads a sample into the sample cache under the specified name.
+    pub fn upload_sample(&self, name: &CStr, spec: &SampleSpec, data: Vec<u8>) -> PaSampleUploadFuture<M> {
+        PaSampleUploadFuture::new(self, name, spec, data)
+    }
+
+    /// Plays a sample from the sample cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: Name of the sample.
+    /// * `device`: Sink to play the sample on. If `None`, the default sink is used.
+    /// * `volume`: Volume to play the sample with. If `None`, the default volume is used.
+    pub fn play_sample(&self, name: &CStr, device: Option<&CStr>, volume: Option<pa_volume_t>) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        let device = device.map_or(null(), |d| d.as_ptr());
+        let volume = volume.unwrap_or(PA_VOLUME_INVALID);
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_context_play_sample(raw, name.as_ptr(), device, volume, Some(cb), userdata)) }
+    }
+
+    /// Plays a sample from the sample cache, attaching properties to the created sink input.
+    ///
+    /// The future resolves to the index of the sink input. See [`play_sample`](#method.play_sample)
+    /// for a description of the other arguments.
+    pub fn play_sample_with_proplist(&self, name: &CStr, device: Option<&CStr>, volume: Option<pa_volume_t>, proplist: &Proplist) -> PaOperationFuture<u32> {
+        let raw = self.raw.get();
+        let device = device.map_or(null(), |d| d.as_ptr());
+        let volume = volume.unwrap_or(PA_VOLUME_INVALID);
+        unsafe { PaOperationFuture::start_index(raw, |cb, userdata| pa_context_play_sample_with_proplist(raw, name.as_ptr(), device, volume, proplist.as_raw(), Some(cb), userdata)) }
+    }
+
+    /// Removes a sample from the sample cache.
+    pub fn remove_sample(&self, name: &CStr) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_success(raw, |cb, userdata| pa_context_remove_sample(raw, name.as_ptr(), Some(cb), userdata)) }
+    }
+
+    /// Returns information about all samples in the sample cache.
+    pub fn get_sample_info_list(&self) -> PaOperationFuture<Vec<SampleInfo>> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_list(raw, |cb, userdata| pa_context_get_sample_info_list(raw, Some(cb), userdata)) }
+    }
+
+    /// Sends a message to an object on the server and returns the response.
+    ///
+    /// Objects are identified by path, e.g. `/core` or `/card/<name>/bluez`. The message
+    /// `list-handlers` sent to `/core` returns all objects and their messages.
+    /// `params` and the response use the message parameter format, see
+    /// [`MessageParam`](enum.MessageParam.html) and [`parse_message_params`](fn.parse_message_params.html).
+    ///
+    /// Requires pulseaudio 15 or newer and the `pa_v15` feature.
+    #[cfg(feature = "pa_v15")]
+    pub fn send_message_to_object(&self, recipient: &CStr, message: &CStr, params: Option<&CStr>) -> PaOperationFuture<String> {
+        let raw = self.raw.get();
+        let params = params.map_or(null(), |p| p.as_ptr());
+        unsafe { PaOperationFuture::start(raw, |userdata| message::pa_context_send_message_to_object(raw, recipient.as_ptr(), message.as_ptr(), params, Some(message::string_cb), userdata)) }
+    }
+
+    /// Returns true if some data is pending to be written to the connection or
+    /// operations are still running.
+    pub fn is_pending(&self) -> bool {
+        unsafe { pa_context_is_pending(self.raw.get()) > 0 }
+    }
+
+    /// Returns whether the connected server is running on the local machine.
+    ///
+    /// Returns `None` if the context is not connected.
+    pub fn is_local(&self) -> Option<bool> {
+        match unsafe { pa_context_is_local(self.raw.get()) } {
+            r if r < 0 => None,
+            r => Some(r != 0),
+        }
+    }
+
+    /// Returns the name of the server the context is connected to.
+    pub fn get_server(&self) -> Option<String> {
+        unsafe { string_from_ptr(pa_context_get_server(self.raw.get())) }
+    }
+
+    /// Returns the protocol version of the library.
+    pub fn get_protocol_version(&self) -> u32 {
+        unsafe { pa_context_get_protocol_version(self.raw.get()) }
+    }
+
+    /// Returns the protocol version of the connected server.
+    ///
+    /// Returns `None` if the context is not connected.
+    pub fn get_server_protocol_version(&self) -> Option<u32> {
+        index_to_option(unsafe { pa_context_get_server_protocol_version(self.raw.get()) })
+    }
+
+    /// Returns the client index this context is identified by on the server.
+    ///
+    /// Returns `None` if the context is not connected.
+    pub fn get_index(&self) -> Option<u32> {
+        index_to_option(unsafe { pa_context_get_index(self.raw.get()) })
+    }
+}
+
+impl<M: PaMainLoopApi + 'static> PaContext<M> {
+    /// Returns a handle that controls this context from other threads.
+    ///
+    /// Commands sent through the handle are run by the mainloop of this context.
+    /// See [`ContextHandle`](struct.ContextHandle.html).
+    ///
+    /// This registers an io event with the mainloop.
+    pub fn handle(&self) -> io::Result<ContextHandle<M>> {
+        handle::new(self)
+    }
 }
 
 impl<M: PaMainLoopApi> Drop for PaContext<M> {
@@ -0,0 +1,104 @@
+use std::ffi::CString;
+use std::fmt;
+
+/// Builder for the argument string of a pulseaudio module.
+///
+/// Values are always quoted, so they may contain whitespace, quotes and
+/// backslashes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleArgs(String);
+
+impl ModuleArgs {
+    /// Creates an empty argument list.
+    pub fn new() -> ModuleArgs {
+        ModuleArgs(String::new())
+    }
+
+    /// Appends an argument.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the key is empty or contains whitespace, `=` or quotes, or if
+    /// the key or the value contain a NUL byte.
+    pub fn arg<K: AsRef<str>, V: AsRef<str>>(mut self, key: K, value: V) -> ModuleArgs {
+        let key = key.as_ref();
+        let value = value.as_ref();
+        assert!(!key.is_empty(), "Module argument keys must not be empty");
+        assert!(!key.contains(|c: char| c.is_whitespace() || c == '=' || c == '"' || c == '\'' || c == '\\' || c == '\0'),
+                "Invalid module argument key: {:?}", key);
+        assert!(!value.contains('\0'), "Module argument values must not contain NUL bytes");
+
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        self.0.push_str(key);
+        self.0.push_str("=\"");
+        for c in value.chars() {
+            if c == '"' || c == '\\' {
+                self.0.push('\\');
+            }
+            self.0.push(c);
+        }
+        self.0.push('"');
+        self
+    }
+
+    /// Returns the argument string as a C string.
+    pub fn to_cstring(&self) -> CString {
+        CString::new(self.0.as_bytes()).unwrap()
+    }
+}
+
+impl fmt::Display for ModuleArgs {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes() {
+        let args = ModuleArgs::new().arg("a", "x\"y\\z");
+        assert_eq!(args.to_string(), "a=\"x\\\"y\\\\z\"");
+        assert_eq!(args.to_cstring().to_bytes(), b"a=\"x\\\"y\\\\z\"");
+    }
+
+    #[test]
+    fn multiple_args() {
+        let args = ModuleArgs::new().arg("sink_name", "my sink").arg("rate", "48000").arg("empty", "");
+        assert_eq!(args.to_string(), "sink_name=\"my sink\" rate=\"48000\" empty=\"\"");
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be empty")]
+    fn empty_key() {
+        ModuleArgs::new().arg("", "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid module argument key")]
+    fn key_with_whitespace() {
+        ModuleArgs::new().arg("a b", "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid module argument key")]
+    fn key_with_equals() {
+        ModuleArgs::new().arg("a=b", "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid module argument key")]
+    fn key_with_quote() {
+        ModuleArgs::new().arg("a\"", "x");
+    }
+
+    #[test]
+    #[should_panic(expected = "must not contain NUL bytes")]
+    fn value_with_nul() {
+        ModuleArgs::new().arg("a", "x\0y");
+    }
+}
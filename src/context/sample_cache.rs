@@ -0,0 +1,41 @@
+use libpulse_sys::*;
+use std::time::Duration;
+
+use operation::FromRawInfo;
+use sample::SampleSpec;
+use util::string_from_ptr;
+
+/// Information about a sample in the sample cache.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SampleInfo {
+    /// Index of the sample.
+    pub index: u32,
+    /// Name of the sample.
+    pub name: String,
+    /// Sample specification, if the sample has been loaded.
+    pub sample_spec: Option<SampleSpec>,
+    /// Duration of the sample.
+    pub duration: Duration,
+    /// Length of the sample in bytes.
+    pub bytes: u32,
+    /// Whether the sample is loaded on demand.
+    pub lazy: bool,
+    /// File name of a lazily loaded sample.
+    pub filename: Option<String>,
+}
+
+impl FromRawInfo for SampleInfo {
+    type Raw = pa_sample_info;
+
+    unsafe fn from_raw(raw: &pa_sample_info) -> SampleInfo {
+        SampleInfo {
+            index: raw.index,
+            name: string_from_ptr(raw.name).unwrap_or_default(),
+            sample_spec: SampleSpec::from_raw(&raw.sample_spec),
+            duration: Duration::from_micros(raw.duration),
+            bytes: raw.bytes,
+            lazy: raw.lazy != 0,
+            filename: string_from_ptr(raw.filename),
+        }
+    }
+}
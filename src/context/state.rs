@@ -1,11 +1,17 @@
-use future_pubsub::unsync as pubsub;
-use futures::prelude::*;
+use futures::{Async, Poll, Stream};
+use futures_core::Stream as StdStream;
 use libc;
 use libpulse_sys::*;
 use refcount::RefCounted;
+#[cfg(feature = "tracing")]
+use std::cell::RefCell;
+use std::pin::Pin;
 use std::ptr::null_mut;
 use std::rc::Rc;
-use std::cell::RefCell;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::{BroadcastReceiver, BroadcastSender, TaskWaker};
+use panic_guard;
 
 /// State of a [`PaContext`](struct.PaContext.html).
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,47 +53,65 @@ pub fn get_state(raw: &RefCounted<pa_context>) -> PaContextState {
 
 struct StateCallbackReceiversImpl {
     raw_ctx: RefCounted<pa_context>,
-    sender: pubsub::UnboundedSender<PaContextState>,
-    receiver: RefCell<pubsub::UnboundedReceiver<PaContextState>>,
+    sender: BroadcastSender<PaContextState>,
+    // Covers the time spent in the current state.
+    #[cfg(feature = "tracing")]
+    span: RefCell<::tracing::Span>,
 }
 
 #[derive(Clone)]
 pub struct StateCallbackReceivers(Rc<StateCallbackReceiversImpl>);
 
 /// A stream for receiving context status updates.
-pub struct PaContextStateStream(pubsub::UnboundedReceiver<PaContextState>);
+///
+/// This type implements both `futures_core::Stream` and `futures` 0.1 `Stream`.
+pub struct PaContextStateStream(BroadcastReceiver<PaContextState>);
 
 impl StateCallbackReceivers {
     pub fn new(raw_ctx: RefCounted<pa_context>) -> StateCallbackReceivers {
-        let (sender, receiver) = pubsub::unbounded();
         let ret = StateCallbackReceivers(Rc::new(StateCallbackReceiversImpl {
             raw_ctx,
-            sender,
-            receiver: RefCell::new(receiver),
+            sender: BroadcastSender::new(),
+            #[cfg(feature = "tracing")]
+            span: RefCell::new(::tracing::Span::none()),
         }));
         unsafe { pa_context_set_state_callback(ret.0.raw_ctx.get(), Some(notify_state_cb), &*(ret.0) as *const _ as *mut libc::c_void) };
         ret
     }
 
     pub fn get_stream(&self) -> PaContextStateStream {
-        PaContextStateStream(self.0.receiver.borrow().clone())
+        PaContextStateStream(self.0.sender.subscribe())
     }
 }
 
 impl StateCallbackReceiversImpl {
     fn send(&self) {
-        self.sender.unbounded_send(get_state(&self.raw_ctx)).unwrap();
-        match self.receiver.borrow_mut().poll() {
-            Ok(Async::Ready(Some(_))) => (),
-            _ => panic!("Could not drain dummy receiver"),
+        let state = get_state(&self.raw_ctx);
+        #[cfg(feature = "tracing")]
+        {
+            // Replacing the span closes the span of the previous state. It is not a child
+            // of the dispatch span, which ends when the callback returns.
+            let span = ::tracing::debug_span!(parent: None, "pa_context_state", ?state);
+            span.in_scope(|| ::tracing::debug!("pulseaudio context state changed"));
+            *self.span.borrow_mut() = span;
         }
+        self.sender.send(state);
     }
 }
 
 extern "C" fn notify_state_cb(_ctx: *mut pa_context, userdata: *mut libc::c_void) {
-    assert!(userdata != null_mut());
-    let data = unsafe { &*(userdata as *const StateCallbackReceiversImpl) };
-    data.send();
+    panic_guard::guard((), || {
+        assert!(userdata != null_mut());
+        let data = unsafe { &*(userdata as *const StateCallbackReceiversImpl) };
+        data.send();
+    })
+}
+
+impl PaContextStateStream {
+    pub(crate) fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Option<PaContextState>> {
+        panic_guard::resume();
+        self.0.poll_next(waker)
+    }
 }
 
 impl Stream for PaContextStateStream {
@@ -95,11 +119,17 @@ impl Stream for PaContextStateStream {
     type Error = ();
 
     fn poll(&mut self) -> Poll<Option<PaContextState>, ()> {
-        match self.0.poll() {
-            Ok(Async::Ready(Some(s))) => Ok(Async::Ready(Some(*s))),
-            Ok(Async::Ready(None)) => Ok(Async::Ready(None)),
-            Ok(Async::NotReady) => Ok(Async::NotReady),
-            Err(()) => Err(()),
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(s) => Ok(Async::Ready(s)),
+            StdPoll::Pending => Ok(Async::NotReady),
         }
     }
 }
+
+impl StdStream for PaContextStateStream {
+    type Item = PaContextState;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Option<PaContextState>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
@@ -2,7 +2,9 @@
 
 use libc;
 use libpulse_sys::*;
+use std::error::Error;
 use std::ffi::CStr;
+use std::fmt;
 
 /// Converts a pulseaudio error code into a static string reference.
 pub fn strerror_ref(error: libc::c_int) -> &'static CStr {
@@ -13,3 +15,30 @@ pub fn strerror_ref(error: libc::c_int) -> &'static CStr {
 pub fn strerror(error: libc::c_int) -> String {
     strerror_ref(error).to_string_lossy().into_owned()
 }
+
+/// A pulseaudio error code.
+///
+/// This is the error type of all operations that can fail on the server side.
+/// It can be converted into a human-readable message via its `Display` implementation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaError(libc::c_int);
+
+impl PaError {
+    /// Creates an error from a pulseaudio error code.
+    pub fn new(code: libc::c_int) -> PaError {
+        PaError(code)
+    }
+
+    /// Returns the pulseaudio error code.
+    pub fn code(&self) -> libc::c_int {
+        self.0
+    }
+}
+
+impl fmt::Display for PaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", strerror_ref(self.0).to_string_lossy())
+    }
+}
+
+impl Error for PaError {}
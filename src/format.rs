@@ -0,0 +1,325 @@
+//! Stream formats, including compressed formats for passthrough.
+use libc;
+use libpulse_sys::*;
+use std::ffi::CStr;
+use std::fmt;
+use std::mem;
+use std::ptr::null;
+
+use sample::{SampleFormat, SampleSpec};
+
+// Added in pulseaudio 13, missing in older bindings.
+const PA_ENCODING_TRUEHD_IEC61937: pa_encoding_t = 7;
+const PA_ENCODING_DTSHD_IEC61937: pa_encoding_t = 8;
+
+/// Encoding of a stream format.
+///
+/// All encodings except `Pcm` are compressed formats that are passed through to the
+/// device, wrapped in IEC 61937 frames.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Any encoding, only valid for queries.
+    Any,
+    /// PCM.
+    Pcm,
+    /// AC3.
+    Ac3Iec61937,
+    /// E-AC3.
+    Eac3Iec61937,
+    /// MPEG-1 or MPEG-2 (Part 3, not AAC).
+    MpegIec61937,
+    /// DTS.
+    DtsIec61937,
+    /// MPEG-2 AAC.
+    Mpeg2AacIec61937,
+    /// Dolby TrueHD.
+    TrueHdIec61937,
+    /// DTS-HD Master Audio.
+    DtsHdIec61937,
+}
+
+impl Encoding {
+    fn new(e: pa_encoding_t) -> Option<Encoding> {
+        match e {
+            PA_ENCODING_ANY => Some(Encoding::Any),
+            PA_ENCODING_PCM => Some(Encoding::Pcm),
+            PA_ENCODING_AC3_IEC61937 => Some(Encoding::Ac3Iec61937),
+            PA_ENCODING_EAC3_IEC61937 => Some(Encoding::Eac3Iec61937),
+            PA_ENCODING_MPEG_IEC61937 => Some(Encoding::MpegIec61937),
+            PA_ENCODING_DTS_IEC61937 => Some(Encoding::DtsIec61937),
+            PA_ENCODING_MPEG2_AAC_IEC61937 => Some(Encoding::Mpeg2AacIec61937),
+            PA_ENCODING_TRUEHD_IEC61937 => Some(Encoding::TrueHdIec61937),
+            PA_ENCODING_DTSHD_IEC61937 => Some(Encoding::DtsHdIec61937),
+            _ => None,
+        }
+    }
+
+    fn to_raw(&self) -> pa_encoding_t {
+        match *self {
+            Encoding::Any => PA_ENCODING_ANY,
+            Encoding::Pcm => PA_ENCODING_PCM,
+            Encoding::Ac3Iec61937 => PA_ENCODING_AC3_IEC61937,
+            Encoding::Eac3Iec61937 => PA_ENCODING_EAC3_IEC61937,
+            Encoding::MpegIec61937 => PA_ENCODING_MPEG_IEC61937,
+            Encoding::DtsIec61937 => PA_ENCODING_DTS_IEC61937,
+            Encoding::Mpeg2AacIec61937 => PA_ENCODING_MPEG2_AAC_IEC61937,
+            Encoding::TrueHdIec61937 => PA_ENCODING_TRUEHD_IEC61937,
+            Encoding::DtsHdIec61937 => PA_ENCODING_DTSHD_IEC61937,
+        }
+    }
+
+    /// Returns the name of the encoding, as used by pulseaudio.
+    ///
+    /// Returns `None` if the encoding is not known to the installed libpulse.
+    pub fn name(&self) -> Option<&'static str> {
+        raw_name(self.to_raw())
+    }
+}
+
+fn raw_name(e: pa_encoding_t) -> Option<&'static str> {
+    let ptr = unsafe { pa_encoding_to_string(e) };
+    if ptr.is_null() {
+        None
+    } else {
+        unsafe { CStr::from_ptr(ptr) }.to_str().ok()
+    }
+}
+
+/// A stream format: an encoding and a list of properties like sample rate and channels.
+///
+/// Streams created with [`PaStream::new_extended`](../stream/struct.PaStream.html#method.new_extended)
+/// offer a list of formats, and the server picks the first one that the sink supports.
+pub struct FormatInfo(*mut pa_format_info);
+
+impl FormatInfo {
+    /// Creates a format without properties.
+    pub fn new(encoding: Encoding) -> FormatInfo {
+        let ptr = unsafe { pa_format_info_new() };
+        assert!(!ptr.is_null());
+        unsafe { (*ptr).encoding = encoding.to_raw() };
+        FormatInfo(ptr)
+    }
+
+    /// Creates a PCM format from a sample specification and an optional channel map.
+    pub fn from_sample_spec(spec: &SampleSpec, map: Option<&pa_channel_map>) -> FormatInfo {
+        let ptr = unsafe { pa_format_info_from_sample_spec(&spec.to_raw(), map.map_or(null(), |m| m)) };
+        assert!(!ptr.is_null());
+        FormatInfo(ptr)
+    }
+
+    /// Copies a raw format.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid format. Panics if `ptr` is null.
+    pub unsafe fn from_raw(ptr: *const pa_format_info) -> FormatInfo {
+        assert!(!ptr.is_null());
+        let copy = pa_format_info_copy(ptr);
+        assert!(!copy.is_null());
+        FormatInfo(copy)
+    }
+
+    /// Returns a raw pointer to the format.
+    ///
+    /// The pointer is valid as long as the format is alive.
+    pub fn as_raw(&self) -> *mut pa_format_info {
+        self.0
+    }
+
+    /// Returns the encoding, or `None` if it is not known to this crate.
+    pub fn encoding(&self) -> Option<Encoding> {
+        Encoding::new(unsafe { (*self.0).encoding })
+    }
+
+    /// Returns true if the encoding is PCM.
+    pub fn is_pcm(&self) -> bool {
+        unsafe { pa_format_info_is_pcm(self.0) != 0 }
+    }
+
+    /// Returns true if the format is valid.
+    pub fn is_valid(&self) -> bool {
+        unsafe { pa_format_info_valid(self.0) != 0 }
+    }
+
+    /// Returns true if this format is compatible with `other`.
+    ///
+    /// The encodings must match, and all properties of this format must be allowed
+    /// by the properties of `other`.
+    pub fn is_compatible(&self, other: &FormatInfo) -> bool {
+        unsafe { pa_format_info_is_compatible(self.0, other.0) != 0 }
+    }
+
+    /// Returns the sample rate property.
+    pub fn get_rate(&self) -> Option<u32> {
+        let mut rate = 0;
+        if unsafe { pa_format_info_get_rate(self.0, &mut rate) } < 0 {
+            None
+        } else {
+            Some(rate)
+        }
+    }
+
+    /// Sets the sample rate property.
+    pub fn set_rate(&mut self, rate: u32) {
+        unsafe { pa_format_info_set_rate(self.0, rate as libc::c_int) };
+    }
+
+    /// Returns the channel count property.
+    pub fn get_channels(&self) -> Option<u8> {
+        let mut channels = 0;
+        if unsafe { pa_format_info_get_channels(self.0, &mut channels) } < 0 {
+            None
+        } else {
+            Some(channels)
+        }
+    }
+
+    /// Sets the channel count property.
+    pub fn set_channels(&mut self, channels: u8) {
+        unsafe { pa_format_info_set_channels(self.0, channels as libc::c_int) };
+    }
+
+    /// Returns the channel map property.
+    pub fn get_channel_map(&self) -> Option<pa_channel_map> {
+        let mut map: pa_channel_map = unsafe { mem::zeroed() };
+        if unsafe { pa_format_info_get_channel_map(self.0, &mut map) } < 0 {
+            None
+        } else {
+            Some(map)
+        }
+    }
+
+    /// Sets the channel map property.
+    pub fn set_channel_map(&mut self, map: &pa_channel_map) {
+        unsafe { pa_format_info_set_channel_map(self.0, map) };
+    }
+
+    /// Returns the sample format property.
+    pub fn get_sample_format(&self) -> Option<SampleFormat> {
+        let mut format = PA_SAMPLE_INVALID;
+        if unsafe { pa_format_info_get_sample_format(self.0, &mut format) } < 0 {
+            None
+        } else {
+            SampleFormat::new(format)
+        }
+    }
+
+    /// Sets the sample format property.
+    pub fn set_sample_format(&mut self, format: SampleFormat) {
+        unsafe { pa_format_info_set_sample_format(self.0, format.to_raw()) };
+    }
+
+    /// Converts a PCM format into a sample specification and channel map.
+    ///
+    /// Returns `None` if the format is not PCM or lacks properties.
+    pub fn to_sample_spec(&self) -> Option<(SampleSpec, pa_channel_map)> {
+        let mut spec: pa_sample_spec = unsafe { mem::zeroed() };
+        let mut map: pa_channel_map = unsafe { mem::zeroed() };
+        if unsafe { pa_format_info_to_sample_spec(self.0, &mut spec, &mut map) } < 0 {
+            None
+        } else {
+            SampleSpec::from_raw(&spec).map(|spec| (spec, map))
+        }
+    }
+}
+
+impl Clone for FormatInfo {
+    fn clone(&self) -> FormatInfo {
+        unsafe { FormatInfo::from_raw(self.0) }
+    }
+}
+
+impl Drop for FormatInfo {
+    fn drop(&mut self) {
+        unsafe { pa_format_info_free(self.0) };
+    }
+}
+
+impl fmt::Debug for FormatInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "FormatInfo({})", self)
+    }
+}
+
+impl fmt::Display for FormatInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut buf = [0 as libc::c_char; PA_FORMAT_INFO_SNPRINT_MAX as usize];
+        let s = unsafe { CStr::from_ptr(pa_format_info_snprint(buf.as_mut_ptr(), buf.len(), self.0)) };
+        f.write_str(&s.to_string_lossy())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stereo() -> pa_channel_map {
+        let mut map: pa_channel_map = unsafe { mem::zeroed() };
+        assert!(!unsafe { pa_channel_map_init_stereo(&mut map) }.is_null());
+        map
+    }
+
+    #[test]
+    fn properties() {
+        let mut format = FormatInfo::new(Encoding::Pcm);
+        assert_eq!(format.encoding(), Some(Encoding::Pcm));
+        assert!(format.is_pcm());
+        assert_eq!(format.get_rate(), None);
+        assert_eq!(format.get_channels(), None);
+        assert!(format.get_channel_map().is_none());
+        assert_eq!(format.get_sample_format(), None);
+
+        format.set_rate(48000);
+        format.set_channels(2);
+        format.set_channel_map(&stereo());
+        format.set_sample_format(SampleFormat::S16le);
+        assert_eq!(format.get_rate(), Some(48000));
+        assert_eq!(format.get_channels(), Some(2));
+        let map = format.get_channel_map().unwrap();
+        assert_ne!(unsafe { pa_channel_map_equal(&map, &stereo()) }, 0);
+        assert_eq!(format.get_sample_format(), Some(SampleFormat::S16le));
+        assert!(format.is_valid());
+
+        let copy = format.clone();
+        assert_eq!(copy.get_rate(), Some(48000));
+    }
+
+    #[test]
+    fn compatible() {
+        let mut a = FormatInfo::new(Encoding::Pcm);
+        a.set_rate(44100);
+        let mut b = FormatInfo::new(Encoding::Pcm);
+        b.set_rate(44100);
+        assert!(a.is_compatible(&b));
+        b.set_rate(48000);
+        assert!(!a.is_compatible(&b));
+
+        // Formats without properties accept any properties of the same encoding.
+        assert!(FormatInfo::new(Encoding::Pcm).is_compatible(&b));
+        assert!(!FormatInfo::new(Encoding::Ac3Iec61937).is_compatible(&b));
+    }
+
+    #[test]
+    fn sample_spec_round_trip() {
+        let spec = SampleSpec::new(SampleFormat::Float32le, 44100, 2);
+        let format = FormatInfo::from_sample_spec(&spec, Some(&stereo()));
+        assert!(format.is_pcm());
+        assert_eq!(format.get_rate(), Some(44100));
+        assert_eq!(format.get_channels(), Some(2));
+
+        let (converted, map) = format.to_sample_spec().unwrap();
+        assert_eq!(converted, spec);
+        assert_ne!(unsafe { pa_channel_map_equal(&map, &stereo()) }, 0);
+
+        assert!(FormatInfo::new(Encoding::Pcm).to_sample_spec().is_none());
+    }
+
+    #[test]
+    fn encoding_names() {
+        assert_eq!(Encoding::Pcm.name(), Some("pcm"));
+        assert_eq!(Encoding::Ac3Iec61937.name(), Some("ac3-iec61937"));
+        // Encodings of newer pulseaudio versions than the installed one have no name.
+        assert_eq!(raw_name(1000), None);
+        assert_eq!(raw_name(PA_ENCODING_INVALID), None);
+    }
+}
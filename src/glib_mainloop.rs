@@ -0,0 +1,68 @@
+//! Wrapper for pulseaudio's GLib mainloop adapter.
+//!
+//! This module is only available if the `glib` feature is enabled. It requires
+//! `libpulse-mainloop-glib`.
+use glib_sys::GMainContext;
+use libpulse_sys::pa_mainloop_api;
+use std::ptr::null_mut;
+use std::rc::Rc;
+
+use mainloop_api::PaMainLoopApi;
+
+#[allow(non_camel_case_types)]
+#[repr(C)]
+struct pa_glib_mainloop {
+    _private: [u8; 0],
+}
+
+#[link(name = "pulse-mainloop-glib")]
+extern "C" {
+    fn pa_glib_mainloop_new(c: *mut GMainContext) -> *mut pa_glib_mainloop;
+    fn pa_glib_mainloop_free(g: *mut pa_glib_mainloop);
+    fn pa_glib_mainloop_get_api(g: *mut pa_glib_mainloop) -> *mut pa_mainloop_api;
+}
+
+struct GlibMainLoopImpl(*mut pa_glib_mainloop);
+
+impl Drop for GlibMainLoopImpl {
+    fn drop(&mut self) {
+        unsafe { pa_glib_mainloop_free(self.0) };
+    }
+}
+
+/// Implementation of [`PaMainLoopApi`] that runs pulseaudio events from a GLib main context.
+///
+/// The events are dispatched whenever the GLib main context is iterated, e.g. by
+/// a running `GMainLoop` or the GTK main loop. All objects attached to this mainloop
+/// must be used from the thread that owns the main context.
+///
+/// [`PaMainLoopApi`]: ../mainloop_api/trait.PaMainLoopApi.html
+#[derive(Clone)]
+pub struct GlibMainLoop {
+    intern: Rc<GlibMainLoopImpl>,
+}
+
+impl GlibMainLoop {
+    /// Creates a new mainloop API attached to the default GLib main context.
+    pub fn new() -> GlibMainLoop {
+        unsafe { GlibMainLoop::with_context(null_mut()) }
+    }
+
+    /// Creates a new mainloop API attached to the specified GLib main context.
+    ///
+    /// # Safety
+    ///
+    /// `context` must be a valid main context or null for the default main context.
+    /// It must outlive the returned mainloop API.
+    pub unsafe fn with_context(context: *mut GMainContext) -> GlibMainLoop {
+        let raw = pa_glib_mainloop_new(context);
+        assert!(raw != null_mut());
+        GlibMainLoop { intern: Rc::new(GlibMainLoopImpl(raw)) }
+    }
+}
+
+unsafe impl PaMainLoopApi for GlibMainLoop {
+    fn get_api(&self) -> *mut pa_mainloop_api {
+        unsafe { pa_glib_mainloop_get_api(self.intern.0) }
+    }
+}
@@ -3,29 +3,83 @@
 //!
 //! This crate provides safe access to the asynchronous API of `libpulse`.
 //! It includes a mainloop API abstraction based on `futures`, `mio` and
+//! `tokio-core`, and, with the `tokio` feature, one based on tokio 1.x.
+//! With the `glib` feature, pulseaudio can also be driven from a GLib main context.
+//! For tests, [`VirtualMainLoop`](virtual_mainloop/struct.VirtualMainLoop.html) runs
+//! on a virtual clock with injected io readiness.
+//! The `stats` feature adds instrumentation to `PaMainLoopApiTokio`.
+//! The `pa_v15` feature enables APIs that require pulseaudio 15 or newer.
+//!
+//! With the `tracing` feature, context and stream states are reported to `tracing` as
+//! spans, and io errors and the dispatch of mainloop events by both tokio backends as
+//! events and spans. The internal log messages of libpulse can be forwarded with
+//! [`pulse_log::forward_to_tracing`](pulse_log/fn.forward_to_tracing.html).
+//!
+//! All futures and streams returned by this crate implement both the
+//! `std::future::Future`/`futures_core::Stream` traits, so they can be
+//! used with `async`/`await`, and the `futures` 0.1 traits for use with
 //! `tokio-core`.
+//!
+//! Panics in callbacks invoked by `libpulse` never unwind into C code. They are
+//! caught and re-raised the next time a mainloop, future or stream of this crate
+//! is polled on the same thread.
 
 extern crate futures;
 extern crate libpulse_sys;
 extern crate libc;
 extern crate mio;
 extern crate tokio_core;
-extern crate future_pubsub;
+extern crate futures_core;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+#[cfg(feature = "glib")]
+extern crate glib_sys;
+#[cfg(feature = "tracing")]
+extern crate tracing;
 
 #[macro_use]
-mod refcount;
+pub mod refcount;
+mod api_vtable;
+mod channel;
 mod explicit_cleanup;
+mod panic_guard;
+mod timeval;
+mod util;
 pub mod context;
 pub mod error;
+pub mod format;
+#[cfg(feature = "glib")]
+pub mod glib_mainloop;
 pub mod mainloop_api;
+pub mod operation;
+pub mod proplist;
+#[cfg(feature = "tracing")]
+pub mod pulse_log;
+pub mod sample;
+pub mod standard_mainloop;
+pub mod stream;
+pub mod threaded_mainloop;
 pub mod tokio_mainloop_api;
+#[cfg(feature = "tokio")]
+pub mod tokio1_mainloop_api;
+pub mod virtual_mainloop;
 
 /// A "prelude" for crates using the `pulseaudio` crate.
 pub mod prelude {
     #[doc(no_inline)]
     pub use mainloop_api::PaMainLoopApi;
+    #[cfg(feature = "glib")]
+    #[doc(no_inline)]
+    pub use glib_mainloop::GlibMainLoop;
+    #[doc(no_inline)]
+    pub use standard_mainloop::StandardMainLoop;
+    #[doc(no_inline)]
+    pub use threaded_mainloop::ThreadedMainLoop;
     #[doc(no_inline)]
     pub use tokio_mainloop_api::PaMainLoopApiTokio;
+    #[cfg(feature = "tokio")]
+    #[doc(no_inline)]
+    pub use tokio1_mainloop_api::PaMainLoopApiTokio1;
     #[doc(no_inline)]
     pub use context::PaContext;
 }
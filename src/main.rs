@@ -31,18 +31,22 @@ fn xmain() -> Result<(), ()> {
                 pulseaudio::context::PaContextState::Terminated => { quit_send.take().unwrap().send(Ok(())).unwrap(); },
                 pulseaudio::context::PaContextState::Ready => {
                     let ctx = ctx.clone();
-                    h.spawn(Timeout::new(Duration::from_secs(5), &h).unwrap().and_then(move |_| {
-                        eprintln!("Disconnecting");
-                        ctx.disconnect();
-                        Ok(())
-                    }).or_else(|_| Err(())));
+                    eprintln!("Connected to {:?} (local: {:?})", ctx.get_server(), ctx.is_local());
+                    h.spawn(Timeout::new(Duration::from_secs(5), &h).unwrap().or_else(|_| Err(())).and_then(move |_| {
+                        eprintln!("Draining");
+                        ctx.drain().then(move |_| {
+                            eprintln!("Disconnecting");
+                            ctx.disconnect();
+                            Ok(())
+                        })
+                    }));
                 }
                 _ => (),
             };
             Ok(())
         }));
     }
-    assert!(ctx.connect(None));
+    core.handle().spawn(ctx.connect(None).map_err(|e| eprintln!("Unable to connect: {}", e)));
 
     core.run(quit_receive).unwrap()
 }
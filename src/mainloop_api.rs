@@ -1,5 +1,13 @@
 //! Rust trait for a pulseaudio mainloop abstraction.
+use futures::{Async, Poll, Stream};
+use futures_core::Stream as StdStream;
+use libc;
 use libpulse_sys::pa_mainloop_api;
+use std::pin::Pin;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::{BroadcastReceiver, TaskWaker};
+use panic_guard;
 
 /// Trait for types that provide a pulseaudio mainloop abstraction.
 ///
@@ -23,3 +31,45 @@ pub unsafe trait PaMainLoopApi: Clone {
     /// Returns a raw pointer to a mainloop API structure.
     fn get_api(&self) -> *mut pa_mainloop_api;
 }
+
+/// A stream of requests to quit the mainloop.
+///
+/// `libpulse` asks the mainloop to quit by calling the `quit` callback of the mainloop
+/// API. Each item is the return value passed to that callback. The stream ends when the
+/// mainloop API is dropped.
+///
+/// This type implements both `futures_core::Stream` and `futures` 0.1 `Stream`.
+pub struct PaQuitRequestStream(BroadcastReceiver<libc::c_int>);
+
+impl PaQuitRequestStream {
+    pub(crate) fn new(receiver: BroadcastReceiver<libc::c_int>) -> PaQuitRequestStream {
+        PaQuitRequestStream(receiver)
+    }
+
+    fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Option<libc::c_int>> {
+        // The quit stream is often the only thing a program polls, so panics from
+        // callbacks must surface here.
+        panic_guard::resume();
+        self.0.poll_next(waker)
+    }
+}
+
+impl Stream for PaQuitRequestStream {
+    type Item = libc::c_int;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<libc::c_int>, ()> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(r) => Ok(Async::Ready(r)),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl StdStream for PaQuitRequestStream {
+    type Item = libc::c_int;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Option<libc::c_int>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
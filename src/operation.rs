@@ -0,0 +1,295 @@
+//! Futures for pulseaudio operations.
+//!
+//! The `start_*` functions of [`PaOperationFuture`](struct.PaOperationFuture.html) turn
+//! callback-based `pa_context_*` functions that are not wrapped by this crate into
+//! futures, passing a matching completion callback to the function. Functions with
+//! other callback signatures can be wrapped with
+//! [`PaOperationFuture::start`](struct.PaOperationFuture.html#method.start) and a custom
+//! callback that calls [`complete`](fn.complete.html).
+use futures::{Async, Future, Poll};
+use libc;
+use libpulse_sys::*;
+use std::cell::{Cell, RefCell};
+use std::future::Future as StdFuture;
+use std::pin::Pin;
+use std::ptr::null_mut;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::{self, Canceled, OneshotReceiver, OneshotSender, TaskWaker};
+use error::PaError;
+use panic_guard;
+use refcount::RefCounted;
+
+/// Userdata passed to the callbacks of a pulseaudio operation.
+///
+/// The data is owned by the operation: it is freed from the operation's
+/// state callback once the operation is done or has been cancelled.
+struct OperationData<T> {
+    sender: Cell<Option<OneshotSender<Result<T, PaError>>>>,
+    pending: RefCell<Option<T>>,
+}
+
+impl<T> OperationData<T> {
+    unsafe fn from_userdata<'a>(userdata: *mut libc::c_void) -> &'a OperationData<T> {
+        assert!(!userdata.is_null());
+        &*(userdata as *const OperationData<T>)
+    }
+
+    fn complete(&self, result: Result<T, PaError>) {
+        if let Some(sender) = self.sender.take() {
+            sender.send(result);
+        }
+    }
+}
+
+/// Conversion from the info structures passed to introspection callbacks.
+pub trait FromRawInfo: Sized {
+    /// The raw info structure.
+    type Raw;
+
+    /// Copies the data out of the raw info structure.
+    unsafe fn from_raw(raw: &Self::Raw) -> Self;
+}
+
+/// A future that resolves when a pulseaudio operation completes.
+///
+/// This type implements both `std::future::Future` and `futures` 0.1 `Future`.
+/// Dropping the future does not cancel the operation on the server.
+#[must_use = "futures do nothing unless polled"]
+pub struct PaOperationFuture<T> {
+    operation: Option<RefCounted<pa_operation>>,
+    receiver: OneshotReceiver<Result<T, PaError>>,
+}
+
+impl<T> PaOperationFuture<T> {
+    /// Starts an operation.
+    ///
+    /// `f` must start the operation, passing the given userdata pointer to a callback
+    /// that completes the operation with the matching result type, and return the
+    /// operation pointer or null on failure. The callback must call
+    /// [`complete`](fn.complete.html). If `f` returns null, the future fails with the error of the context `ctx`, which
+    /// is usually obtained with [`PaContext::raw`](../context/struct.PaContext.html#method.raw).
+    ///
+    /// # Safety
+    ///
+    /// `ctx` must be valid, and the callback must complete the operation with result type `T`.
+    pub unsafe fn start<F>(ctx: *mut pa_context, f: F) -> PaOperationFuture<T>
+        where F: FnOnce(*mut libc::c_void) -> *mut pa_operation
+    {
+        let (sender, receiver) = channel::oneshot();
+        let data = Box::into_raw(Box::new(OperationData {
+            sender: Cell::new(Some(sender)),
+            pending: RefCell::new(None),
+        }));
+        let op = f(data as *mut libc::c_void);
+        if op.is_null() {
+            Box::from_raw(data).complete(Err(PaError::new(pa_context_errno(ctx))));
+            PaOperationFuture { operation: None, receiver }
+        } else {
+            pa_operation_set_state_callback(op, Some(operation_state_cb::<T>), data as *mut libc::c_void);
+            PaOperationFuture { operation: Some(RefCounted::new(op)), receiver }
+        }
+    }
+
+    /// Returns the reference to the underlying `pa_operation`.
+    ///
+    /// Returns `None` if the operation could not be started or has completed.
+    pub fn operation(&self) -> Option<&RefCounted<pa_operation>> {
+        self.operation.as_ref()
+    }
+
+    /// Returns a future that is already completed with the given result.
+    pub fn ready(result: Result<T, PaError>) -> PaOperationFuture<T> {
+        let (sender, receiver) = channel::oneshot();
+        sender.send(result);
+        PaOperationFuture { operation: None, receiver }
+    }
+
+    pub(crate) fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Result<T, PaError>> {
+        panic_guard::resume();
+        let res = match self.receiver.poll(waker) {
+            StdPoll::Ready(Ok(res)) => res,
+            StdPoll::Ready(Err(Canceled)) => Err(PaError::new(PA_ERR_CONNECTIONTERMINATED as libc::c_int)),
+            StdPoll::Pending => return StdPoll::Pending,
+        };
+        self.operation = None;
+        StdPoll::Ready(res)
+    }
+}
+
+impl<T> Future for PaOperationFuture<T> {
+    type Item = T;
+    type Error = PaError;
+
+    fn poll(&mut self) -> Poll<T, PaError> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(res) => res.map(Async::Ready),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<T> StdFuture for PaOperationFuture<T> {
+    type Output = Result<T, PaError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Result<T, PaError>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
+
+impl PaOperationFuture<()> {
+    /// Starts an operation that only reports its completion, like `pa_context_drain`.
+    ///
+    /// `f` must pass the given callback and userdata to the libpulse function.
+    ///
+    /// # Safety
+    ///
+    /// See [`start`](#method.start).
+    pub unsafe fn start_notify<F>(ctx: *mut pa_context, f: F) -> PaOperationFuture<()>
+        where F: FnOnce(extern "C" fn(*mut pa_context, *mut libc::c_void), *mut libc::c_void) -> *mut pa_operation
+    {
+        Self::start(ctx, |userdata| f(notify_cb, userdata))
+    }
+
+    /// Starts an operation that reports success or failure, like `pa_context_set_name`.
+    ///
+    /// `f` must pass the given callback and userdata to the libpulse function.
+    ///
+    /// # Safety
+    ///
+    /// See [`start`](#method.start).
+    pub unsafe fn start_success<F>(ctx: *mut pa_context, f: F) -> PaOperationFuture<()>
+        where F: FnOnce(extern "C" fn(*mut pa_context, libc::c_int, *mut libc::c_void), *mut libc::c_void) -> *mut pa_operation
+    {
+        Self::start(ctx, |userdata| f(success_cb, userdata))
+    }
+
+    /// Starts a stream operation that reports success or failure, like `pa_stream_cork`.
+    ///
+    /// `f` must pass the given callback and userdata to the libpulse function. `ctx`
+    /// is the context of the stream.
+    ///
+    /// # Safety
+    ///
+    /// See [`start`](#method.start).
+    pub unsafe fn start_stream_success<F>(ctx: *mut pa_context, f: F) -> PaOperationFuture<()>
+        where F: FnOnce(extern "C" fn(*mut pa_stream, libc::c_int, *mut libc::c_void), *mut libc::c_void) -> *mut pa_operation
+    {
+        Self::start(ctx, |userdata| f(stream_success_cb, userdata))
+    }
+}
+
+impl PaOperationFuture<u32> {
+    /// Starts an operation that returns an object index, like `pa_context_load_module`.
+    ///
+    /// `f` must pass the given callback and userdata to the libpulse function.
+    ///
+    /// # Safety
+    ///
+    /// See [`start`](#method.start).
+    pub unsafe fn start_index<F>(ctx: *mut pa_context, f: F) -> PaOperationFuture<u32>
+        where F: FnOnce(extern "C" fn(*mut pa_context, u32, *mut libc::c_void), *mut libc::c_void) -> *mut pa_operation
+    {
+        Self::start(ctx, |userdata| f(index_cb, userdata))
+    }
+}
+
+impl<I: FromRawInfo> PaOperationFuture<Vec<I>> {
+    /// Starts an introspection operation that returns a list of objects, like
+    /// `pa_context_get_card_info_list`.
+    ///
+    /// `f` must pass the given callback and userdata to the libpulse function.
+    ///
+    /// # Safety
+    ///
+    /// See [`start`](#method.start). The info structure passed to the callback must
+    /// be `I::Raw`.
+    pub unsafe fn start_list<F>(ctx: *mut pa_context, f: F) -> PaOperationFuture<Vec<I>>
+        where F: FnOnce(extern "C" fn(*mut pa_context, *const I::Raw, libc::c_int, *mut libc::c_void), *mut libc::c_void) -> *mut pa_operation
+    {
+        Self::start(ctx, |userdata| f(list_cb::<I>, userdata))
+    }
+}
+
+extern "C" fn operation_state_cb<T>(op: *mut pa_operation, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        if unsafe { pa_operation_get_state(op) } != PA_OPERATION_RUNNING {
+            unsafe { pa_operation_set_state_callback(op, None, null_mut()) };
+            drop(unsafe { Box::from_raw(userdata as *mut OperationData<T>) });
+        }
+    })
+}
+
+/// Completes the operation that owns `userdata` with the given result.
+///
+/// Only the first call has an effect. To be called from the completion callback of an
+/// operation started with [`PaOperationFuture::start`](struct.PaOperationFuture.html#method.start).
+/// Callbacks should not panic, panics must not unwind into libpulse.
+///
+/// # Safety
+///
+/// `userdata` must be the userdata pointer of a running operation whose result type is `T`.
+pub unsafe fn complete<T>(userdata: *mut libc::c_void, result: Result<T, PaError>) {
+    OperationData::<T>::from_userdata(userdata).complete(result);
+}
+
+/// Completion callback for operations that only report their completion.
+extern "C" fn notify_cb(_ctx: *mut pa_context, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        unsafe { OperationData::<()>::from_userdata(userdata) }.complete(Ok(()));
+    })
+}
+
+/// Completion callback for operations that report success or failure.
+extern "C" fn success_cb(ctx: *mut pa_context, success: libc::c_int, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        let data = unsafe { OperationData::<()>::from_userdata(userdata) };
+        if success != 0 {
+            data.complete(Ok(()));
+        } else {
+            data.complete(Err(PaError::new(unsafe { pa_context_errno(ctx) })));
+        }
+    })
+}
+
+/// Completion callback for stream operations that report success or failure.
+extern "C" fn stream_success_cb(s: *mut pa_stream, success: libc::c_int, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        let data = unsafe { OperationData::<()>::from_userdata(userdata) };
+        if success != 0 {
+            data.complete(Ok(()));
+        } else {
+            data.complete(Err(PaError::new(unsafe { pa_context_errno(pa_stream_get_context(s)) })));
+        }
+    })
+}
+
+/// Completion callback for operations that return an object index.
+extern "C" fn index_cb(ctx: *mut pa_context, idx: u32, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        let data = unsafe { OperationData::<u32>::from_userdata(userdata) };
+        if idx != PA_INVALID_INDEX {
+            data.complete(Ok(idx));
+        } else {
+            data.complete(Err(PaError::new(unsafe { pa_context_errno(ctx) })));
+        }
+    })
+}
+
+/// Callback for introspection operations that return a list of objects.
+extern "C" fn list_cb<I: FromRawInfo>(ctx: *mut pa_context, info: *const I::Raw, eol: libc::c_int, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        let data = unsafe { OperationData::<Vec<I>>::from_userdata(userdata) };
+        if eol < 0 {
+            data.complete(Err(PaError::new(unsafe { pa_context_errno(ctx) })));
+        } else if eol > 0 {
+            let items = data.pending.borrow_mut().take().unwrap_or_default();
+            data.complete(Ok(items));
+        } else if !info.is_null() {
+            let item = unsafe { I::from_raw(&*info) };
+            data.pending.borrow_mut().get_or_insert_with(Vec::new).push(item);
+        }
+    })
+}
+
+pa_refcountable!(pa_operation, pa_operation_ref, pa_operation_unref);
@@ -0,0 +1,53 @@
+//! Keeps panics from unwinding into the C code of libpulse.
+//!
+//! Unwinding through a C stack frame is undefined behaviour, so every `extern "C"`
+//! callback of this crate runs its body through [`guard`]. A panic is caught and stored
+//! for the current thread, and re-raised by [`resume`] the next time a mainloop, future
+//! or stream of this crate is polled on that thread.
+//!
+//! Threads that never poll, like the event loop thread of a threaded mainloop, can
+//! redirect their panics into a [`PanicSlot`] with [`set_thread_slot`], so they can
+//! be re-raised on another thread.
+use std::any::Any;
+use std::cell::RefCell;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+
+/// Storage for a panic that is re-raised on another thread.
+pub type PanicSlot = Arc<Mutex<Option<Box<Any + Send>>>>;
+
+thread_local!(static PANIC: RefCell<Option<Box<Any + Send>>> = RefCell::new(None));
+thread_local!(static SLOT: RefCell<Option<PanicSlot>> = RefCell::new(None));
+
+/// Stores the panics caught on the current thread in `slot`.
+pub fn set_thread_slot(slot: PanicSlot) {
+    SLOT.with(|s| *s.borrow_mut() = Some(slot));
+}
+
+fn store(p: &mut Option<Box<Any + Send>>, payload: Box<Any + Send>) {
+    // Later panics are usually a consequence of the first one.
+    if p.is_none() {
+        *p = Some(payload);
+    }
+}
+
+/// Runs `f`, returning `default` if it panics.
+pub fn guard<R, F>(default: R, f: F) -> R where F: FnOnce() -> R {
+    match panic::catch_unwind(AssertUnwindSafe(f)) {
+        Ok(r) => r,
+        Err(payload) => {
+            match SLOT.with(|s| s.borrow().clone()) {
+                Some(slot) => store(&mut slot.lock().unwrap(), payload),
+                None => PANIC.with(|p| store(&mut p.borrow_mut(), payload)),
+            }
+            default
+        }
+    }
+}
+
+/// Re-raises a panic that was caught in a callback on this thread.
+pub fn resume() {
+    if let Some(payload) = PANIC.with(|p| p.borrow_mut().take()) {
+        panic::resume_unwind(payload);
+    }
+}
@@ -0,0 +1,111 @@
+//! Property lists.
+use libpulse_sys::*;
+use std::ffi::CStr;
+use std::mem;
+use std::ptr::null;
+
+/// A list of key-value properties attached to pulseaudio objects.
+///
+/// Commonly used keys are documented in the
+/// [pulseaudio documentation](https://freedesktop.org/software/pulseaudio/doxygen/proplist_8h.html).
+pub struct Proplist(*mut pa_proplist);
+
+impl Proplist {
+    /// Creates an empty property list.
+    pub fn new() -> Proplist {
+        let ptr = unsafe { pa_proplist_new() };
+        assert!(!ptr.is_null());
+        Proplist(ptr)
+    }
+
+    /// Sets a string property.
+    ///
+    /// Returns false if the key is not valid.
+    pub fn set(&mut self, key: &CStr, value: &CStr) -> bool {
+        unsafe { pa_proplist_sets(self.0, key.as_ptr(), value.as_ptr()) >= 0 }
+    }
+
+    /// Returns a string property.
+    ///
+    /// Returns `None` if the property does not exist or is not a string.
+    pub fn get(&self, key: &CStr) -> Option<&CStr> {
+        let ptr = unsafe { pa_proplist_gets(self.0, key.as_ptr()) };
+        if ptr == null() {
+            None
+        } else {
+            Some(unsafe { CStr::from_ptr(ptr) })
+        }
+    }
+
+    /// Removes a property.
+    ///
+    /// Returns false if the property did not exist.
+    pub fn unset(&mut self, key: &CStr) -> bool {
+        unsafe { pa_proplist_unset(self.0, key.as_ptr()) >= 0 }
+    }
+
+    /// Returns true if the property list contains the key.
+    pub fn contains(&self, key: &CStr) -> bool {
+        unsafe { pa_proplist_contains(self.0, key.as_ptr()) > 0 }
+    }
+
+    /// Returns the number of properties.
+    pub fn len(&self) -> usize {
+        unsafe { pa_proplist_size(self.0) as usize }
+    }
+
+    /// Returns true if the property list is empty.
+    pub fn is_empty(&self) -> bool {
+        unsafe { pa_proplist_isempty(self.0) != 0 }
+    }
+
+    /// Takes ownership of a raw property list.
+    ///
+    /// Property lists are not reference counted, the list is freed when the returned
+    /// value is dropped.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid property list that is not owned by anything else.
+    /// Panics if `ptr` is null.
+    pub unsafe fn from_raw(ptr: *mut pa_proplist) -> Proplist {
+        assert!(!ptr.is_null());
+        Proplist(ptr)
+    }
+
+    /// Returns the raw pointer and transfers ownership to the caller.
+    ///
+    /// The caller must free the list with `pa_proplist_free`.
+    pub fn into_raw(self) -> *mut pa_proplist {
+        let ptr = self.0;
+        mem::forget(self);
+        ptr
+    }
+
+    /// Returns a raw pointer to the property list.
+    ///
+    /// The pointer is valid as long as the property list is alive.
+    pub fn as_raw(&self) -> *mut pa_proplist {
+        self.0
+    }
+}
+
+impl Default for Proplist {
+    fn default() -> Proplist {
+        Proplist::new()
+    }
+}
+
+impl Clone for Proplist {
+    fn clone(&self) -> Proplist {
+        let ptr = unsafe { pa_proplist_copy(self.0) };
+        assert!(!ptr.is_null());
+        Proplist(ptr)
+    }
+}
+
+impl Drop for Proplist {
+    fn drop(&mut self) {
+        unsafe { pa_proplist_free(self.0) };
+    }
+}
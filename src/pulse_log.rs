@@ -0,0 +1,174 @@
+//! Forwarding of the internal log messages of libpulse to `tracing`.
+//!
+//! libpulse logs warnings and errors to stderr. Its log functions are not part of the
+//! public API and have no callback target, so they are looked up at runtime, and the
+//! log is redirected into a pipe that is read by a thread named `pulse-log`. Each line
+//! is emitted as a `tracing` event with the target `libpulse`.
+//!
+//! Only available with the `tracing` feature.
+use libc;
+use std::ffi::CString;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::mem;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::thread;
+use tracing::Level;
+
+// Levels of `pa_log_level_t`.
+const PA_LOG_ERROR: libc::c_int = 0;
+const PA_LOG_WARN: libc::c_int = 1;
+const PA_LOG_INFO: libc::c_int = 3;
+const PA_LOG_DEBUG: libc::c_int = 4;
+
+struct LogFunctions {
+    parse_target: unsafe extern "C" fn(*const libc::c_char) -> *mut libc::c_void,
+    // Returns an int in newer versions, which is ignored.
+    set_target: unsafe extern "C" fn(*mut libc::c_void),
+    target_free: unsafe extern "C" fn(*mut libc::c_void),
+    set_level: unsafe extern "C" fn(libc::c_int),
+}
+
+impl LogFunctions {
+    // The functions are exported by libpulsecommon, which is loaded with libpulse.
+    fn lookup() -> Option<LogFunctions> {
+        unsafe {
+            Some(LogFunctions {
+                parse_target: mem::transmute(symbol(b"pa_log_parse_target\0")?),
+                set_target: mem::transmute(symbol(b"pa_log_set_target\0")?),
+                target_free: mem::transmute(symbol(b"pa_log_target_free\0")?),
+                set_level: mem::transmute(symbol(b"pa_log_set_level\0")?),
+            })
+        }
+    }
+}
+
+unsafe fn symbol(name: &[u8]) -> Option<*mut libc::c_void> {
+    let ptr = libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr() as *const libc::c_char);
+    if ptr.is_null() {
+        None
+    } else {
+        Some(ptr)
+    }
+}
+
+/// Forwards the log messages of libpulse with at least the given level to `tracing`.
+///
+/// libpulse has no separate trace level, `Level::TRACE` enables its debug messages.
+/// The log configuration of libpulse is global and not thread safe, so this should be
+/// called before any context is created. Environment variables like `PULSE_LOG` take
+/// precedence.
+///
+/// Returns an error if the log functions of the installed libpulse cannot be found, or
+/// the pipe cannot be created.
+pub fn forward_to_tracing(level: Level) -> io::Result<()> {
+    let functions = LogFunctions::lookup()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "libpulse log functions not found"))?;
+    let [read_fd, write_fd] = pipe()?;
+    let reader = unsafe { File::from_raw_fd(read_fd) };
+    // libpulse opens the pipe again, so the thread sees EOF when libpulse closes it.
+    let writer = unsafe { File::from_raw_fd(write_fd) };
+
+    let target = CString::new(format!("file:/dev/fd/{}", write_fd)).unwrap();
+    let pa_level = match level {
+        Level::ERROR => PA_LOG_ERROR,
+        Level::WARN => PA_LOG_WARN,
+        Level::INFO => PA_LOG_INFO,
+        _ => PA_LOG_DEBUG,
+    };
+    unsafe {
+        let raw_target = (functions.parse_target)(target.as_ptr());
+        if raw_target.is_null() {
+            return Err(io::Error::new(io::ErrorKind::Other, "libpulse rejected the log target"));
+        }
+        (functions.set_level)(pa_level);
+        (functions.set_target)(raw_target);
+        (functions.target_free)(raw_target);
+    }
+    drop(writer);
+
+    thread::Builder::new()
+        .name("pulse-log".into())
+        .spawn(move || {
+            for line in BufReader::new(reader).lines() {
+                match line {
+                    Ok(line) => emit(&line),
+                    Err(_) => break,
+                }
+            }
+        })?;
+    Ok(())
+}
+
+fn emit(line: &str) {
+    let (level, message) = parse(line);
+    match level {
+        Level::ERROR => ::tracing::error!(target: "libpulse", "{}", message),
+        Level::WARN => ::tracing::warn!(target: "libpulse", "{}", message),
+        Level::DEBUG => ::tracing::debug!(target: "libpulse", "{}", message),
+        _ => ::tracing::info!(target: "libpulse", "{}", message),
+    }
+}
+
+// Lines start with the level, e.g. `W: message`, unless libpulse was configured to
+// print a timestamp first. Such lines are logged as info.
+fn parse(line: &str) -> (Level, &str) {
+    let bytes = line.as_bytes();
+    if bytes.len() < 3 || &bytes[1..3] != b": " {
+        return (Level::INFO, line);
+    }
+    let level = match bytes[0] {
+        b'E' => Level::ERROR,
+        b'W' => Level::WARN,
+        b'D' => Level::DEBUG,
+        _ => Level::INFO,
+    };
+    (level, &line[3..])
+}
+
+fn pipe() -> io::Result<[RawFd; 2]> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    for fd in fds.iter() {
+        if unsafe { libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC) } < 0 {
+            let e = io::Error::last_os_error();
+            unsafe {
+                libc::close(fds[0]);
+                libc::close(fds[1]);
+            }
+            return Err(e);
+        }
+    }
+    Ok(fds)
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+    use super::parse;
+
+    #[test]
+    fn levels() {
+        assert_eq!(parse("E: failed"), (Level::ERROR, "failed"));
+        assert_eq!(parse("W: careful"), (Level::WARN, "careful"));
+        assert_eq!(parse("D: details"), (Level::DEBUG, "details"));
+        assert_eq!(parse("I: note"), (Level::INFO, "note"));
+        assert_eq!(parse("N: notice"), (Level::INFO, "notice"));
+    }
+
+    #[test]
+    fn timestamped() {
+        let line = "(   0.001|   0.000) W: careful";
+        assert_eq!(parse(line), (Level::INFO, line));
+    }
+
+    #[test]
+    fn short() {
+        assert_eq!(parse(""), (Level::INFO, ""));
+        assert_eq!(parse("E"), (Level::INFO, "E"));
+        assert_eq!(parse("E:"), (Level::INFO, "E:"));
+        assert_eq!(parse("E: "), (Level::ERROR, ""));
+    }
+}
@@ -1,46 +1,109 @@
+//! Reference counted libpulse objects.
+//!
+//! This module allows mixing the safe types of this crate with direct calls into
+//! `libpulse_sys`. A [`RefCounted`](struct.RefCounted.html) owns one reference to a
+//! libpulse object. The raw pointer can be obtained with
+//! [`get`](struct.RefCounted.html#method.get), and raw pointers returned by libpulse
+//! can be adopted with [`new`](struct.RefCounted.html#method.new) or
+//! [`new_ref`](struct.RefCounted.html#method.new_ref).
+use std::mem;
 use std::ptr::null_mut;
 
+/// A libpulse object with reference counting functions.
+///
+/// Implemented for `pa_context`, `pa_stream` and `pa_operation`.
 pub unsafe trait RefCountable {
-    fn incref(ptr: *mut Self);
-    fn decref(ptr: *mut Self);
+    /// Increments the reference count of the object.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid object.
+    unsafe fn incref(ptr: *mut Self);
+    /// Decrements the reference count of the object, freeing it when the count drops
+    /// to zero.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid object, and the caller must own the reference that
+    /// is released.
+    unsafe fn decref(ptr: *mut Self);
 }
 
+/// An owned reference to a reference counted libpulse object.
+///
+/// Cloning increments the reference count, dropping decrements it.
 #[derive(Debug)]
 pub struct RefCounted<T: RefCountable>(*mut T);
 
 impl<T: RefCountable> RefCounted<T> {
+    /// Takes ownership of a reference to the object.
+    ///
+    /// Use this for pointers returned by libpulse functions that transfer a reference
+    /// to the caller, like `pa_stream_new` or the `pa_context_*` operation functions.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid object, and the caller must own the reference that
+    /// is transferred. Panics if `ptr` is null.
     pub unsafe fn new(ptr: *mut T) -> RefCounted<T> {
         assert!(ptr != null_mut());
         RefCounted(ptr)
     }
 
+    /// Adds a new reference to the object.
+    ///
+    /// Use this for pointers borrowed from libpulse, like the ones passed to callbacks.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a valid object. Panics if `ptr` is null.
+    pub unsafe fn new_ref(ptr: *mut T) -> RefCounted<T> {
+        assert!(ptr != null_mut());
+        RefCountable::incref(ptr);
+        RefCounted(ptr)
+    }
+
+    /// Returns the raw pointer.
+    ///
+    /// The pointer is valid as long as this reference is alive. No reference is
+    /// transferred to the caller.
     pub fn get(&self) -> *mut T {
         self.0
     }
+
+    /// Returns the raw pointer and transfers the reference to the caller.
+    ///
+    /// The caller is responsible for releasing the reference, for example with
+    /// [`new`](#method.new) or the matching `*_unref` function.
+    pub fn into_raw(self) -> *mut T {
+        let ptr = self.0;
+        mem::forget(self);
+        ptr
+    }
 }
 
 impl<T: RefCountable> Clone for RefCounted<T> {
   fn clone(&self) -> Self {
-      RefCountable::incref(self.0);
+      unsafe { RefCountable::incref(self.0) };
       RefCounted(self.0)
   }
 }
 
 impl<T: RefCountable> Drop for RefCounted<T> {
     fn drop(&mut self) {
-        RefCountable::decref(self.0);
+        unsafe { RefCountable::decref(self.0) };
     }
 }
 
 macro_rules! pa_refcountable {
     ($t:ident, $incref:ident, $decref:ident) => {
         unsafe impl ::refcount::RefCountable for $t {
-            fn incref(ptr: *mut $t) {
-                unsafe { $incref(ptr) };
+            unsafe fn incref(ptr: *mut $t) {
+                $incref(ptr);
             }
 
-            fn decref(ptr: *mut $t) {
-                unsafe { $decref(ptr) };
+            unsafe fn decref(ptr: *mut $t) {
+                $decref(ptr);
             }
         }
     }
@@ -0,0 +1,121 @@
+//! Sample format specifications.
+use libpulse_sys::*;
+
+/// Format of a single audio sample.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SampleFormat {
+    /// Unsigned 8 bit PCM.
+    U8,
+    /// 8 bit a-Law.
+    Alaw,
+    /// 8 bit mu-Law.
+    Ulaw,
+    /// Signed 16 bit PCM, little endian.
+    S16le,
+    /// Signed 16 bit PCM, big endian.
+    S16be,
+    /// 32 bit IEEE floating point, little endian, range -1.0 to 1.0.
+    Float32le,
+    /// 32 bit IEEE floating point, big endian, range -1.0 to 1.0.
+    Float32be,
+    /// Signed 32 bit PCM, little endian.
+    S32le,
+    /// Signed 32 bit PCM, big endian.
+    S32be,
+    /// Signed 24 bit PCM packed, little endian.
+    S24le,
+    /// Signed 24 bit PCM packed, big endian.
+    S24be,
+    /// Signed 24 bit PCM in LSB of 32 bit words, little endian.
+    S24_32le,
+    /// Signed 24 bit PCM in LSB of 32 bit words, big endian.
+    S24_32be,
+}
+
+impl SampleFormat {
+    pub(crate) fn new(f: pa_sample_format_t) -> Option<SampleFormat> {
+        match f {
+            PA_SAMPLE_U8 => Some(SampleFormat::U8),
+            PA_SAMPLE_ALAW => Some(SampleFormat::Alaw),
+            PA_SAMPLE_ULAW => Some(SampleFormat::Ulaw),
+            PA_SAMPLE_S16LE => Some(SampleFormat::S16le),
+            PA_SAMPLE_S16BE => Some(SampleFormat::S16be),
+            PA_SAMPLE_FLOAT32LE => Some(SampleFormat::Float32le),
+            PA_SAMPLE_FLOAT32BE => Some(SampleFormat::Float32be),
+            PA_SAMPLE_S32LE => Some(SampleFormat::S32le),
+            PA_SAMPLE_S32BE => Some(SampleFormat::S32be),
+            PA_SAMPLE_S24LE => Some(SampleFormat::S24le),
+            PA_SAMPLE_S24BE => Some(SampleFormat::S24be),
+            PA_SAMPLE_S24_32LE => Some(SampleFormat::S24_32le),
+            PA_SAMPLE_S24_32BE => Some(SampleFormat::S24_32be),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn to_raw(&self) -> pa_sample_format_t {
+        match *self {
+            SampleFormat::U8 => PA_SAMPLE_U8,
+            SampleFormat::Alaw => PA_SAMPLE_ALAW,
+            SampleFormat::Ulaw => PA_SAMPLE_ULAW,
+            SampleFormat::S16le => PA_SAMPLE_S16LE,
+            SampleFormat::S16be => PA_SAMPLE_S16BE,
+            SampleFormat::Float32le => PA_SAMPLE_FLOAT32LE,
+            SampleFormat::Float32be => PA_SAMPLE_FLOAT32BE,
+            SampleFormat::S32le => PA_SAMPLE_S32LE,
+            SampleFormat::S32be => PA_SAMPLE_S32BE,
+            SampleFormat::S24le => PA_SAMPLE_S24LE,
+            SampleFormat::S24be => PA_SAMPLE_S24BE,
+            SampleFormat::S24_32le => PA_SAMPLE_S24_32LE,
+            SampleFormat::S24_32be => PA_SAMPLE_S24_32BE,
+        }
+    }
+}
+
+/// A sample format and attribute specification.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SampleSpec {
+    /// The sample format.
+    pub format: SampleFormat,
+    /// The sample rate in Hz.
+    pub rate: u32,
+    /// Number of audio channels.
+    pub channels: u8,
+}
+
+impl SampleSpec {
+    /// Creates a new sample specification.
+    pub fn new(format: SampleFormat, rate: u32, channels: u8) -> SampleSpec {
+        SampleSpec { format, rate, channels }
+    }
+
+    /// Returns true if the specification is valid.
+    pub fn is_valid(&self) -> bool {
+        unsafe { pa_sample_spec_valid(&self.to_raw()) != 0 }
+    }
+
+    /// Returns the size of a frame in bytes.
+    pub fn frame_size(&self) -> usize {
+        unsafe { pa_frame_size(&self.to_raw()) }
+    }
+
+    /// Returns the number of bytes per second of audio.
+    pub fn bytes_per_second(&self) -> usize {
+        unsafe { pa_bytes_per_second(&self.to_raw()) }
+    }
+
+    /// Converts a raw sample specification.
+    ///
+    /// Returns `None` if the sample format is invalid.
+    pub fn from_raw(raw: &pa_sample_spec) -> Option<SampleSpec> {
+        SampleFormat::new(raw.format).map(|format| SampleSpec::new(format, raw.rate, raw.channels))
+    }
+
+    /// Converts the specification into the raw pulseaudio structure.
+    pub fn to_raw(&self) -> pa_sample_spec {
+        pa_sample_spec {
+            format: self.format.to_raw(),
+            rate: self.rate,
+            channels: self.channels,
+        }
+    }
+}
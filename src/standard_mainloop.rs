@@ -0,0 +1,183 @@
+//! Wrapper for pulseaudio's own mainloop implementation.
+use libc;
+use libpulse_sys::*;
+use std::cell::RefCell;
+use std::cmp;
+use std::ptr::null_mut;
+use std::rc::Rc;
+use std::slice;
+use std::time::Duration;
+
+use mainloop_api::PaMainLoopApi;
+use panic_guard;
+
+/// Reason why a mainloop iteration could not dispatch events.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IterateError {
+    /// The mainloop was asked to quit with the given return value.
+    Quit(libc::c_int),
+    /// An error occurred while polling.
+    Failed,
+}
+
+type PollFunc = Box<FnMut(&mut [libc::pollfd], Option<Duration>) -> libc::c_int>;
+
+struct StandardMainLoopImpl {
+    raw: *mut pa_mainloop,
+    poll_func: RefCell<Option<PollFunc>>,
+}
+
+impl Drop for StandardMainLoopImpl {
+    fn drop(&mut self) {
+        unsafe { pa_mainloop_free(self.raw) };
+    }
+}
+
+/// Implementation of [`PaMainLoopApi`] using pulseaudio's own `pa_mainloop`.
+///
+/// This mainloop does not depend on any other event loop. It has to be driven
+/// explicitly, either with [`run`](#method.run), [`iterate`](#method.iterate), or by calling
+/// [`prepare`](#method.prepare), [`poll`](#method.poll) and [`dispatch`](#method.dispatch) in turn.
+///
+/// Futures returned by this crate are completed from the pulseaudio callbacks, so they
+/// have to be polled from the same thread, e.g. with a local executor that is run between
+/// mainloop iterations.
+///
+/// [`PaMainLoopApi`]: ../mainloop_api/trait.PaMainLoopApi.html
+#[derive(Clone)]
+pub struct StandardMainLoop {
+    intern: Rc<StandardMainLoopImpl>,
+}
+
+impl StandardMainLoop {
+    /// Creates a new mainloop.
+    pub fn new() -> StandardMainLoop {
+        let raw = unsafe { pa_mainloop_new() };
+        assert!(raw != null_mut());
+        StandardMainLoop {
+            intern: Rc::new(StandardMainLoopImpl { raw, poll_func: RefCell::new(None) }),
+        }
+    }
+
+    /// Runs a single iteration of the mainloop.
+    ///
+    /// If `block` is true, waits for events if none are pending. Returns the number
+    /// of dispatched events.
+    pub fn iterate(&self, block: bool) -> Result<u32, IterateError> {
+        let mut retval = 0;
+        let r = unsafe { pa_mainloop_iterate(self.intern.raw, block as libc::c_int, &mut retval) };
+        self.check(r).map_err(|e| match e {
+            IterateError::Quit(_) => IterateError::Quit(retval),
+            e => e,
+        })
+    }
+
+    /// Runs the mainloop until [`quit`](#method.quit) is called.
+    ///
+    /// Returns the value passed to [`quit`](#method.quit), or an error if polling failed.
+    pub fn run(&self) -> Result<libc::c_int, ()> {
+        let mut retval = 0;
+        let r = unsafe { pa_mainloop_run(self.intern.raw, &mut retval) };
+        panic_guard::resume();
+        // pa_mainloop_run returns 1 after quit and a negative value on errors.
+        if r >= 0 {
+            Ok(retval)
+        } else {
+            Err(())
+        }
+    }
+
+    /// Asks the mainloop to quit with the given return value.
+    pub fn quit(&self, retval: libc::c_int) {
+        unsafe { pa_mainloop_quit(self.intern.raw, retval) }
+    }
+
+    /// Returns the return value passed to [`quit`](#method.quit).
+    pub fn get_retval(&self) -> libc::c_int {
+        unsafe { pa_mainloop_get_retval(self.intern.raw) }
+    }
+
+    /// Prepares for a single iteration of the mainloop.
+    ///
+    /// `timeout` is the maximum time the following [`poll`](#method.poll) may block.
+    /// If it is `None`, the time is not limited.
+    pub fn prepare(&self, timeout: Option<Duration>) -> Result<(), IterateError> {
+        let timeout = match timeout {
+            Some(t) => cmp::min(t.as_secs().saturating_mul(1000000).saturating_add(t.subsec_micros() as u64),
+                                libc::c_int::max_value() as u64) as libc::c_int,
+            None => -1,
+        };
+        let r = unsafe { pa_mainloop_prepare(self.intern.raw, timeout) };
+        self.check(r).map(|_| ())
+    }
+
+    /// Executes the poll step of a mainloop iteration.
+    ///
+    /// Returns the number of file descriptors that are ready.
+    pub fn poll(&self) -> Result<u32, IterateError> {
+        let r = unsafe { pa_mainloop_poll(self.intern.raw) };
+        self.check(r)
+    }
+
+    /// Dispatches the events that are ready after [`poll`](#method.poll).
+    ///
+    /// Returns the number of dispatched events.
+    pub fn dispatch(&self) -> Result<u32, IterateError> {
+        let r = unsafe { pa_mainloop_dispatch(self.intern.raw) };
+        self.check(r)
+    }
+
+    /// Interrupts a running poll.
+    pub fn wakeup(&self) {
+        unsafe { pa_mainloop_wakeup(self.intern.raw) }
+    }
+
+    /// Replaces the function the mainloop uses to wait for events.
+    ///
+    /// The function receives the file descriptors to wait for and the timeout. It must
+    /// behave like `poll()`: fill in the `revents` fields and return the number of ready
+    /// file descriptors or -1 on error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within the current poll function.
+    pub fn set_poll_func<F>(&self, f: F) where F: FnMut(&mut [libc::pollfd], Option<Duration>) -> libc::c_int + 'static {
+        *self.intern.poll_func.borrow_mut() = Some(Box::new(f));
+        unsafe { pa_mainloop_set_poll_func(self.intern.raw, Some(poll_func_cb), &*self.intern as *const StandardMainLoopImpl as *mut libc::c_void) };
+    }
+
+    fn check(&self, r: libc::c_int) -> Result<u32, IterateError> {
+        panic_guard::resume();
+        match r {
+            -2 => Err(IterateError::Quit(self.get_retval())),
+            r if r < 0 => Err(IterateError::Failed),
+            r => Ok(r as u32),
+        }
+    }
+}
+
+unsafe impl PaMainLoopApi for StandardMainLoop {
+    fn get_api(&self) -> *mut pa_mainloop_api {
+        unsafe { pa_mainloop_get_api(self.intern.raw) }
+    }
+}
+
+extern "C" fn poll_func_cb(ufds: *mut pollfd, nfds: libc::c_ulong, timeout: libc::c_int, userdata: *mut libc::c_void) -> libc::c_int {
+    panic_guard::guard(-1, || {
+        assert!(userdata != null_mut());
+        let data = unsafe { &*(userdata as *const StandardMainLoopImpl) };
+        let mut f = data.poll_func.borrow_mut();
+        let f = f.as_mut().unwrap();
+        let fds = if nfds == 0 {
+            &mut []
+        } else {
+            unsafe { slice::from_raw_parts_mut(ufds as *mut libc::pollfd, nfds as usize) }
+        };
+        let timeout = if timeout < 0 {
+            None
+        } else {
+            Some(Duration::from_millis(timeout as u64))
+        };
+        f(fds, timeout)
+    })
+}
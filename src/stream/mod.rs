@@ -0,0 +1,256 @@
+//! Pulseaudio streams.
+mod moved;
+mod peak;
+mod state;
+mod upload;
+
+use libc;
+use libpulse_sys::*;
+use std::ffi::CStr;
+use std::ptr::{null, null_mut};
+
+use context::PaContext;
+use error::PaError;
+use format::FormatInfo;
+use mainloop_api::PaMainLoopApi;
+use operation::PaOperationFuture;
+use proplist::Proplist;
+use refcount::RefCounted;
+use sample::SampleSpec;
+use util::{index_to_option, string_from_ptr};
+
+pub use self::moved::PaStreamMovedStream;
+pub use self::peak::{PaPeakStream, PeakSource};
+pub use self::state::PaStreamState;
+pub use self::state::PaStreamStateStream;
+pub use self::upload::PaSampleUploadFuture;
+use ::explicit_cleanup::ExplicitCleanup;
+
+/// An audio stream for playback, recording or sample upload.
+///
+/// If the stream is still connected when it is dropped, it is disconnected.
+pub struct PaStream<M: PaMainLoopApi> {
+    raw: ExplicitCleanup<RefCounted<pa_stream>>,
+    context: PaContext<M>,
+    state_cb_receivers: ExplicitCleanup<state::StateCallbackReceivers>,
+    moved_cb_receivers: ExplicitCleanup<moved::MovedCallbackReceivers>,
+}
+
+impl<M: PaMainLoopApi> PaStream<M> {
+    /// Creates a new, unconnected stream.
+    ///
+    /// # Arguments
+    ///
+    /// * `context`: The context to create the stream in.
+    /// * `name`: Stream name. For upload streams, this is the name of the sample.
+    /// * `spec`: Sample specification of the stream.
+    pub fn new(context: &PaContext<M>, name: &CStr, spec: &SampleSpec) -> Result<PaStream<M>, PaError> {
+        unsafe {
+            let ptr = pa_stream_new(context.raw().get(), name.as_ptr(), &spec.to_raw(), null());
+            if ptr == null_mut() {
+                return Err(PaError::new(context.errno()));
+            }
+            Ok(PaStream::from_raw(context, RefCounted::new(ptr)))
+        }
+    }
+
+    /// Creates a new, unconnected stream that offers a list of formats.
+    ///
+    /// When the stream is connected to a playback device, the server picks the first
+    /// format the sink supports. This allows passing compressed formats through to the
+    /// device, with a PCM fallback. The negotiated format is returned by
+    /// [`get_format_info`](#method.get_format_info).
+    pub fn new_extended(context: &PaContext<M>, name: &CStr, formats: &[FormatInfo], proplist: Option<&Proplist>) -> Result<PaStream<M>, PaError> {
+        let mut raw_formats: Vec<*mut pa_format_info> = formats.iter().map(|f| f.as_raw()).collect();
+        let proplist = proplist.map_or(null_mut(), |p| p.as_raw());
+        unsafe {
+            let ptr = pa_stream_new_extended(context.raw().get(), name.as_ptr(), raw_formats.as_mut_ptr(), raw_formats.len() as u32, proplist);
+            if ptr == null_mut() {
+                return Err(PaError::new(context.errno()));
+            }
+            Ok(PaStream::from_raw(context, RefCounted::new(ptr)))
+        }
+    }
+
+    /// Wraps a stream created with libpulse functions that are not wrapped by this crate.
+    ///
+    /// The state and moved callbacks of the stream are replaced.
+    ///
+    /// # Safety
+    ///
+    /// The stream must belong to `context`.
+    pub unsafe fn from_raw(context: &PaContext<M>, raw: RefCounted<pa_stream>) -> PaStream<M> {
+        let state_cb_receivers = state::StateCallbackReceivers::new(raw.clone());
+        let moved_cb_receivers = moved::MovedCallbackReceivers::new(raw.clone());
+        PaStream {
+            raw: ExplicitCleanup::new(raw),
+            context: context.clone(),
+            state_cb_receivers: ExplicitCleanup::new(state_cb_receivers),
+            moved_cb_receivers: ExplicitCleanup::new(moved_cb_receivers),
+        }
+    }
+
+    /// Returns the reference to the underlying `pa_stream`.
+    ///
+    /// This allows calling libpulse functions that are not wrapped by this crate.
+    pub fn raw(&self) -> &RefCounted<pa_stream> {
+        &self.raw
+    }
+
+    /// Returns the context this stream belongs to.
+    pub fn get_context(&self) -> &PaContext<M> {
+        &self.context
+    }
+
+    /// Returns the current stream state.
+    pub fn get_state(&self) -> PaStreamState {
+        state::get_state(&self.raw)
+    }
+
+    /// Returns a stream that notifies of stream state changes.
+    pub fn get_state_stream(&self) -> PaStreamStateStream {
+        self.state_cb_receivers.get_stream()
+    }
+
+    /// Returns the sink input or source output index of the stream on the server.
+    ///
+    /// Returns `None` if the stream is not connected.
+    pub fn get_index(&self) -> Option<u32> {
+        index_to_option(unsafe { pa_stream_get_index(self.raw.get()) })
+    }
+
+    /// Returns the name of the sink or source the stream is connected to.
+    ///
+    /// Returns `None` if the stream is not connected.
+    pub fn get_device_name(&self) -> Option<String> {
+        unsafe { string_from_ptr(pa_stream_get_device_name(self.raw.get())) }
+    }
+
+    /// Returns the index of the sink or source the stream is connected to.
+    ///
+    /// Returns `None` if the stream is not connected.
+    pub fn get_device_index(&self) -> Option<u32> {
+        index_to_option(unsafe { pa_stream_get_device_index(self.raw.get()) })
+    }
+
+    /// Returns a stream that notifies when the stream is moved to another sink or source.
+    pub fn get_moved_stream(&self) -> PaStreamMovedStream {
+        self.moved_cb_receivers.get_stream()
+    }
+
+    /// Returns whether the sink or source the stream is connected to is suspended.
+    ///
+    /// Returns `None` if the stream is not connected.
+    pub fn is_suspended(&self) -> Option<bool> {
+        match unsafe { pa_stream_is_suspended(self.raw.get()) } {
+            r if r < 0 => None,
+            r => Some(r != 0),
+        }
+    }
+
+    /// Returns whether the stream is corked.
+    ///
+    /// Returns `None` if the stream is not connected.
+    pub fn is_corked(&self) -> Option<bool> {
+        match unsafe { pa_stream_is_corked(self.raw.get()) } {
+            r if r < 0 => None,
+            r => Some(r != 0),
+        }
+    }
+
+    /// Changes the sample rate of the stream while it is playing or recording.
+    ///
+    /// The stream must have been connected with `PA_STREAM_VARIABLE_RATE`, e.g. with
+    /// [`connect_playback`](#method.connect_playback). This allows adjusting the playback
+    /// rate to track a remote clock.
+    pub fn update_sample_rate(&self, rate: u32) -> PaOperationFuture<()> {
+        let raw = self.raw.get();
+        unsafe { PaOperationFuture::start_stream_success(self.context.raw().get(), |cb, userdata| pa_stream_update_sample_rate(raw, rate, Some(cb), userdata)) }
+    }
+
+    /// Returns the format of the stream.
+    ///
+    /// Returns `None` if the stream is not connected yet.
+    pub fn get_format_info(&self) -> Option<FormatInfo> {
+        let ptr = unsafe { pa_stream_get_format_info(self.raw.get()) };
+        if ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { FormatInfo::from_raw(ptr) })
+        }
+    }
+
+    /// Connects the stream for playback.
+    ///
+    /// # Arguments
+    ///
+    /// * `device`: Sink to play on. If `None`, the server picks the sink.
+    /// * `attr`: Buffer attributes. If `None`, the server picks defaults.
+    /// * `flags`: Stream flags, e.g. `PA_STREAM_VARIABLE_RATE` to allow
+    ///   [`update_sample_rate`](#method.update_sample_rate).
+    pub fn connect_playback(&self, device: Option<&CStr>, attr: Option<&pa_buffer_attr>, flags: pa_stream_flags_t) -> Result<(), PaError> {
+        let device = device.map_or(null(), |d| d.as_ptr());
+        let attr = attr.map_or(null(), |a| a as *const _);
+        self.check(unsafe { pa_stream_connect_playback(self.raw.get(), device, attr, flags, null(), null_mut()) })
+    }
+
+    /// Connects the stream for recording.
+    ///
+    /// # Arguments
+    ///
+    /// * `device`: Source to record from. If `None`, the server picks the source.
+    /// * `attr`: Buffer attributes. If `None`, the server picks defaults.
+    /// * `flags`: Stream flags.
+    pub fn connect_record(&self, device: Option<&CStr>, attr: Option<&pa_buffer_attr>, flags: pa_stream_flags_t) -> Result<(), PaError> {
+        let device = device.map_or(null(), |d| d.as_ptr());
+        let attr = attr.map_or(null(), |a| a as *const _);
+        self.check(unsafe { pa_stream_connect_record(self.raw.get(), device, attr, flags) })
+    }
+
+    /// Connects the stream to the sample cache for uploading a sample of `length` bytes.
+    pub fn connect_upload(&self, length: usize) -> Result<(), PaError> {
+        self.check(unsafe { pa_stream_connect_upload(self.raw.get(), length) })
+    }
+
+    /// Finishes a sample upload.
+    ///
+    /// The stream changes to [`Terminated`](enum.PaStreamState.html#variant.Terminated)
+    /// once the sample has been stored.
+    pub fn finish_upload(&self) -> Result<(), PaError> {
+        self.check(unsafe { pa_stream_finish_upload(self.raw.get()) })
+    }
+
+    /// Writes data to the stream.
+    ///
+    /// The data is copied into an internal buffer.
+    pub fn write(&self, data: &[u8]) -> Result<(), PaError> {
+        self.check(unsafe { pa_stream_write(self.raw.get(), data.as_ptr() as *const libc::c_void, data.len(), None, 0, PA_SEEK_RELATIVE) })
+    }
+
+    /// Disconnects the stream.
+    pub fn disconnect(&self) -> Result<(), PaError> {
+        self.check(unsafe { pa_stream_disconnect(self.raw.get()) })
+    }
+
+    fn check(&self, r: libc::c_int) -> Result<(), PaError> {
+        if r < 0 {
+            Err(PaError::new(self.context.errno()))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<M: PaMainLoopApi> Drop for PaStream<M> {
+    fn drop(&mut self) {
+        ExplicitCleanup::cleanup(&mut self.state_cb_receivers);
+        ExplicitCleanup::cleanup(&mut self.moved_cb_receivers);
+        match self.get_state() {
+            PaStreamState::Creating | PaStreamState::Ready => drop(self.disconnect()),
+            _ => (),
+        }
+        ExplicitCleanup::cleanup(&mut self.raw);
+    }
+}
+
+pa_refcountable!(pa_stream, pa_stream_ref, pa_stream_unref);
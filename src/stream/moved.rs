@@ -0,0 +1,95 @@
+use futures::{Async, Poll, Stream};
+use futures_core::Stream as StdStream;
+use libc;
+use libpulse_sys::*;
+use refcount::RefCounted;
+use std::pin::Pin;
+use std::ptr::null_mut;
+use std::rc::Rc;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::{BroadcastReceiver, BroadcastSender, TaskWaker};
+use panic_guard;
+
+struct MovedCallbackReceiversImpl {
+    raw_stream: RefCounted<pa_stream>,
+    sender: BroadcastSender<u32>,
+}
+
+#[derive(Clone)]
+pub struct MovedCallbackReceivers(Rc<MovedCallbackReceiversImpl>);
+
+/// A stream that notifies when a [`PaStream`](struct.PaStream.html) is moved to another
+/// sink or source.
+///
+/// Yields the index of the new device. The name of the device is returned by
+/// [`PaStream::get_device_name`](struct.PaStream.html#method.get_device_name).
+///
+/// This type implements both `futures_core::Stream` and `futures` 0.1 `Stream`.
+pub struct PaStreamMovedStream(BroadcastReceiver<u32>);
+
+impl MovedCallbackReceivers {
+    pub fn new(raw_stream: RefCounted<pa_stream>) -> MovedCallbackReceivers {
+        let ret = MovedCallbackReceivers(Rc::new(MovedCallbackReceiversImpl {
+            raw_stream,
+            sender: BroadcastSender::new(),
+        }));
+        unsafe { pa_stream_set_moved_callback(ret.0.raw_stream.get(), Some(notify_moved_cb), &*(ret.0) as *const _ as *mut libc::c_void) };
+        ret
+    }
+
+    pub fn get_stream(&self) -> PaStreamMovedStream {
+        PaStreamMovedStream(self.0.sender.subscribe())
+    }
+}
+
+impl MovedCallbackReceiversImpl {
+    fn send(&self) {
+        let index = unsafe { pa_stream_get_device_index(self.raw_stream.get()) };
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(device = index, "pulseaudio stream moved");
+        self.sender.send(index);
+    }
+}
+
+impl Drop for MovedCallbackReceiversImpl {
+    fn drop(&mut self) {
+        // The context may keep the stream alive after we are gone.
+        unsafe { pa_stream_set_moved_callback(self.raw_stream.get(), None, null_mut()) };
+    }
+}
+
+extern "C" fn notify_moved_cb(_s: *mut pa_stream, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        assert!(userdata != null_mut());
+        let data = unsafe { &*(userdata as *const MovedCallbackReceiversImpl) };
+        data.send();
+    })
+}
+
+impl PaStreamMovedStream {
+    fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Option<u32>> {
+        panic_guard::resume();
+        self.0.poll_next(waker)
+    }
+}
+
+impl Stream for PaStreamMovedStream {
+    type Item = u32;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<u32>, ()> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(s) => Ok(Async::Ready(s)),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl StdStream for PaStreamMovedStream {
+    type Item = u32;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Option<u32>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
@@ -0,0 +1,232 @@
+use futures::{Async, Poll, Stream};
+use futures_core::Stream as StdStream;
+use libc;
+use libpulse_sys::*;
+use std::cell::Cell;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::pin::Pin;
+use std::ptr::{null, null_mut};
+use std::slice;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::TaskWaker;
+use context::PaContext;
+use error::PaError;
+use mainloop_api::PaMainLoopApi;
+use operation::{FromRawInfo, PaOperationFuture};
+use panic_guard;
+use refcount::RefCounted;
+use sample::{SampleFormat, SampleSpec};
+use super::{PaStream, PaStreamState, PaStreamStateStream};
+
+#[cfg(target_endian = "little")]
+const FLOAT32NE: SampleFormat = SampleFormat::Float32le;
+#[cfg(target_endian = "big")]
+const FLOAT32NE: SampleFormat = SampleFormat::Float32be;
+
+/// The audio that a [`PaPeakStream`](struct.PaPeakStream.html) measures.
+#[derive(Debug, Clone, Copy)]
+pub enum PeakSource<'a> {
+    /// A source, by name or index.
+    Source(&'a CStr),
+    /// A sink, by name. The monitor source of the sink is looked up on the
+    /// server and recorded.
+    Sink(&'a CStr),
+    /// A single sink input, by index.
+    SinkInput(u32),
+}
+
+// The monitor source from the info of a sink.
+struct SinkMonitor(Option<CString>);
+
+impl FromRawInfo for SinkMonitor {
+    type Raw = pa_sink_info;
+
+    unsafe fn from_raw(raw: &pa_sink_info) -> SinkMonitor {
+        if raw.monitor_source_name.is_null() {
+            SinkMonitor(None)
+        } else {
+            SinkMonitor(Some(CStr::from_ptr(raw.monitor_source_name).to_owned()))
+        }
+    }
+}
+
+struct ReadCallback {
+    raw_stream: RefCounted<pa_stream>,
+    // The maximum level since the last poll.
+    peak: Cell<Option<f32>>,
+    waker: Cell<Option<TaskWaker>>,
+}
+
+impl ReadCallback {
+    fn read(&self) {
+        let stream = self.raw_stream.get();
+        loop {
+            let mut data = null();
+            let mut length = 0;
+            if unsafe { pa_stream_peek(stream, &mut data, &mut length) } < 0 || length == 0 {
+                return;
+            }
+            if !data.is_null() {
+                let peaks = unsafe { slice::from_raw_parts(data as *const f32, length / mem::size_of::<f32>()) };
+                if !peaks.is_empty() {
+                    let max = peaks.iter().cloned().fold(self.peak.get().unwrap_or(0.0), f32::max);
+                    self.peak.set(Some(max.min(1.0)));
+                    if let Some(waker) = self.waker.take() {
+                        waker.wake();
+                    }
+                }
+            }
+            unsafe { pa_stream_drop(stream) };
+        }
+    }
+}
+
+impl Drop for ReadCallback {
+    fn drop(&mut self) {
+        unsafe { pa_stream_set_readcallback(self.raw_stream.get(), None, null_mut()) };
+    }
+}
+
+extern "C" fn read_cb(_s: *mut pa_stream, _nbytes: usize, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        assert!(userdata != null_mut());
+        let data = unsafe { &*(userdata as *const ReadCallback) };
+        data.read();
+    })
+}
+
+/// A stream of peak levels, as shown by volume meters.
+///
+/// Audio is recorded with peak detection enabled: the server only sends the peak
+/// of each block of samples, `rate` times per second. The levels are normalized to
+/// the range 0.0 to 1.0. If the stream is not polled fast enough, each poll yields the
+/// maximum level since the previous poll.
+///
+/// The stream ends when the recording stream fails or terminates, e.g. because the
+/// monitored sink input was removed. For [`PeakSource::Sink`](enum.PeakSource.html),
+/// recording starts once the monitor source has been looked up, which requires the
+/// stream to be polled; the stream ends if the sink does not exist.
+///
+/// This type implements both `futures_core::Stream` and `futures` 0.1 `Stream`.
+pub struct PaPeakStream<M: PaMainLoopApi> {
+    states: Option<PaStreamStateStream>,
+    // The lookup of the monitor source of a sink, before the stream is connected.
+    monitor: Option<PaOperationFuture<Vec<SinkMonitor>>>,
+    callback: Box<ReadCallback>,
+    stream: PaStream<M>,
+}
+
+impl<M: PaMainLoopApi> PaPeakStream<M> {
+    /// Starts measuring peak levels of `source`, `rate` times per second.
+    pub fn new(context: &PaContext<M>, source: PeakSource, rate: u32) -> Result<PaPeakStream<M>, PaError> {
+        let spec = SampleSpec::new(FLOAT32NE, rate, 1);
+        let stream = PaStream::new(context, CStr::from_bytes_with_nul(b"Peak detect\0").unwrap(), &spec)?;
+        let raw = stream.raw().get();
+
+        let callback = Box::new(ReadCallback { raw_stream: stream.raw().clone(), peak: Cell::new(None), waker: Cell::new(None) });
+        let states = stream.get_state_stream();
+        unsafe { pa_stream_set_readcallback(raw, Some(read_cb), &*callback as *const _ as *mut libc::c_void) };
+
+        let mut monitor = None;
+        match source {
+            PeakSource::Source(name) => connect(&stream, Some(name))?,
+            PeakSource::Sink(name) => {
+                let ctx = context.raw().get();
+                monitor = Some(unsafe {
+                    PaOperationFuture::start_list(ctx, |cb, userdata| pa_context_get_sink_info_by_name(ctx, name.as_ptr(), Some(cb), userdata))
+                });
+            },
+            PeakSource::SinkInput(index) => {
+                // The server records from the monitor of the sink the input is playing on.
+                if unsafe { pa_stream_set_monitor_stream(raw, index) } < 0 {
+                    return Err(PaError::new(context.errno()));
+                }
+                connect(&stream, None)?;
+            },
+        }
+
+        Ok(PaPeakStream { states: Some(states), monitor: monitor, callback: callback, stream: stream })
+    }
+
+    // Connects the stream once the monitor source of the sink is known.
+    fn poll_monitor(&mut self, waker: &TaskWaker) -> StdPoll<Result<(), ()>> {
+        let result = match self.monitor {
+            Some(ref mut monitor) => match monitor.poll_impl(waker) {
+                StdPoll::Ready(result) => result,
+                StdPoll::Pending => return StdPoll::Pending,
+            },
+            None => return StdPoll::Ready(Ok(())),
+        };
+        self.monitor = None;
+        let device = match result.ok().and_then(|sinks| sinks.into_iter().next()) {
+            Some(SinkMonitor(Some(device))) => device,
+            _ => return StdPoll::Ready(Err(())),
+        };
+        StdPoll::Ready(connect(&self.stream, Some(&device)).map_err(|_| ()))
+    }
+
+    fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Option<f32>> {
+        panic_guard::resume();
+        if self.states.is_some() {
+            match self.poll_monitor(waker) {
+                StdPoll::Ready(Ok(())) => (),
+                StdPoll::Ready(Err(())) => self.states = None,
+                StdPoll::Pending => return StdPoll::Pending,
+            }
+        }
+        if let Some(peak) = self.callback.peak.take() {
+            return StdPoll::Ready(Some(peak));
+        }
+        self.callback.waker.set(Some(waker.clone()));
+        loop {
+            let state = match self.states {
+                Some(ref mut states) => states.poll_impl(waker),
+                None => return StdPoll::Ready(None),
+            };
+            match state {
+                StdPoll::Ready(Some(PaStreamState::Failed)) | StdPoll::Ready(Some(PaStreamState::Terminated)) | StdPoll::Ready(None) => {
+                    self.states = None;
+                    return StdPoll::Ready(None);
+                },
+                StdPoll::Ready(Some(_)) => (),
+                StdPoll::Pending => return StdPoll::Pending,
+            }
+        }
+    }
+}
+
+fn connect<M: PaMainLoopApi>(stream: &PaStream<M>, device: Option<&CStr>) -> Result<(), PaError> {
+    let attr = pa_buffer_attr {
+        maxlength: u32::max_value(),
+        tlength: u32::max_value(),
+        prebuf: u32::max_value(),
+        minreq: u32::max_value(),
+        fragsize: mem::size_of::<f32>() as u32,
+    };
+    let flags = PA_STREAM_PEAK_DETECT | PA_STREAM_ADJUST_LATENCY | PA_STREAM_DONT_MOVE | PA_STREAM_DONT_INHIBIT_AUTO_SUSPEND;
+    stream.connect_record(device, Some(&attr), flags)
+}
+
+impl<M: PaMainLoopApi> Stream for PaPeakStream<M> {
+    type Item = f32;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<f32>, ()> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(p) => Ok(Async::Ready(p)),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<M: PaMainLoopApi> StdStream for PaPeakStream<M> {
+    type Item = f32;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Option<f32>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
+
+impl<M: PaMainLoopApi> Unpin for PaPeakStream<M> {}
@@ -0,0 +1,136 @@
+use futures::{Async, Poll, Stream};
+use futures_core::Stream as StdStream;
+use libc;
+use libpulse_sys::*;
+use refcount::RefCounted;
+#[cfg(feature = "tracing")]
+use std::cell::RefCell;
+use std::pin::Pin;
+use std::ptr::null_mut;
+use std::rc::Rc;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::{BroadcastReceiver, BroadcastSender, TaskWaker};
+use panic_guard;
+
+/// State of a [`PaStream`](struct.PaStream.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaStreamState {
+    /// The stream is not yet connected to any sink or source.
+    Unconnected,
+    /// The stream is being created.
+    Creating,
+    /// The stream is established, you may pass audio data to it now.
+    Ready,
+    /// An error occurred that made the stream invalid.
+    Failed,
+    /// The stream has been terminated cleanly.
+    Terminated
+}
+
+impl PaStreamState {
+    fn new(s: pa_stream_state_t) -> Result<PaStreamState, ()> {
+        match s {
+            PA_STREAM_UNCONNECTED => Ok(PaStreamState::Unconnected),
+            PA_STREAM_CREATING => Ok(PaStreamState::Creating),
+            PA_STREAM_READY => Ok(PaStreamState::Ready),
+            PA_STREAM_FAILED => Ok(PaStreamState::Failed),
+            PA_STREAM_TERMINATED => Ok(PaStreamState::Terminated),
+            _ => Err(())
+        }
+    }
+}
+
+pub fn get_state(raw: &RefCounted<pa_stream>) -> PaStreamState {
+    PaStreamState::new(unsafe { pa_stream_get_state(raw.get()) }).unwrap()
+}
+
+struct StateCallbackReceiversImpl {
+    raw_stream: RefCounted<pa_stream>,
+    sender: BroadcastSender<PaStreamState>,
+    // Covers the time spent in the current state.
+    #[cfg(feature = "tracing")]
+    span: RefCell<::tracing::Span>,
+}
+
+#[derive(Clone)]
+pub struct StateCallbackReceivers(Rc<StateCallbackReceiversImpl>);
+
+/// A stream for receiving stream state updates.
+///
+/// This type implements both `futures_core::Stream` and `futures` 0.1 `Stream`.
+pub struct PaStreamStateStream(BroadcastReceiver<PaStreamState>);
+
+impl StateCallbackReceivers {
+    pub fn new(raw_stream: RefCounted<pa_stream>) -> StateCallbackReceivers {
+        let ret = StateCallbackReceivers(Rc::new(StateCallbackReceiversImpl {
+            raw_stream,
+            sender: BroadcastSender::new(),
+            #[cfg(feature = "tracing")]
+            span: RefCell::new(::tracing::Span::none()),
+        }));
+        unsafe { pa_stream_set_state_callback(ret.0.raw_stream.get(), Some(notify_state_cb), &*(ret.0) as *const _ as *mut libc::c_void) };
+        ret
+    }
+
+    pub fn get_stream(&self) -> PaStreamStateStream {
+        PaStreamStateStream(self.0.sender.subscribe())
+    }
+}
+
+impl StateCallbackReceiversImpl {
+    fn send(&self) {
+        let state = get_state(&self.raw_stream);
+        #[cfg(feature = "tracing")]
+        {
+            // Replacing the span closes the span of the previous state. It is not a child
+            // of the dispatch span, which ends when the callback returns.
+            let span = ::tracing::debug_span!(parent: None, "pa_stream_state", ?state);
+            span.in_scope(|| ::tracing::debug!("pulseaudio stream state changed"));
+            *self.span.borrow_mut() = span;
+        }
+        self.sender.send(state);
+    }
+}
+
+impl Drop for StateCallbackReceiversImpl {
+    fn drop(&mut self) {
+        // The context may keep the stream alive after we are gone.
+        unsafe { pa_stream_set_state_callback(self.raw_stream.get(), None, null_mut()) };
+    }
+}
+
+extern "C" fn notify_state_cb(_s: *mut pa_stream, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        assert!(userdata != null_mut());
+        let data = unsafe { &*(userdata as *const StateCallbackReceiversImpl) };
+        data.send();
+    })
+}
+
+impl PaStreamStateStream {
+    pub(crate) fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Option<PaStreamState>> {
+        panic_guard::resume();
+        self.0.poll_next(waker)
+    }
+}
+
+impl Stream for PaStreamStateStream {
+    type Item = PaStreamState;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<PaStreamState>, ()> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(s) => Ok(Async::Ready(s)),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl StdStream for PaStreamStateStream {
+    type Item = PaStreamState;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Option<PaStreamState>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
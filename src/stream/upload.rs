@@ -0,0 +1,96 @@
+use futures::{Async, Future, Poll};
+use libc;
+use libpulse_sys::*;
+use std::ffi::CStr;
+use std::future::Future as StdFuture;
+use std::mem;
+use std::pin::Pin;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::TaskWaker;
+use context::PaContext;
+use error::PaError;
+use mainloop_api::PaMainLoopApi;
+use panic_guard;
+use sample::SampleSpec;
+use super::{PaStream, PaStreamState, PaStreamStateStream};
+
+enum UploadState<M: PaMainLoopApi> {
+    Uploading(PaStream<M>, PaStreamStateStream, Option<Vec<u8>>),
+    Failed(PaError),
+    Done,
+}
+
+/// A future that uploads a sample into the sample cache.
+///
+/// The future completes once the server has stored the sample. Dropping the future
+/// before that aborts the upload.
+///
+/// This type implements both `std::future::Future` and `futures` 0.1 `Future`.
+#[must_use = "futures do nothing unless polled"]
+pub struct PaSampleUploadFuture<M: PaMainLoopApi>(UploadState<M>);
+
+impl<M: PaMainLoopApi> PaSampleUploadFuture<M> {
+    /// Starts uploading `data` into the sample cache under the name `name`.
+    pub fn new(context: &PaContext<M>, name: &CStr, spec: &SampleSpec, data: Vec<u8>) -> PaSampleUploadFuture<M> {
+        let stream = match PaStream::new(context, name, spec) {
+            Ok(stream) => stream,
+            Err(e) => return PaSampleUploadFuture(UploadState::Failed(e)),
+        };
+        let states = stream.get_state_stream();
+        match stream.connect_upload(data.len()) {
+            Ok(()) => PaSampleUploadFuture(UploadState::Uploading(stream, states, Some(data))),
+            Err(e) => PaSampleUploadFuture(UploadState::Failed(e)),
+        }
+    }
+
+    fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Result<(), PaError>> {
+        panic_guard::resume();
+        match mem::replace(&mut self.0, UploadState::Done) {
+            UploadState::Uploading(stream, mut states, mut data) => {
+                loop {
+                    match states.poll_impl(waker) {
+                        StdPoll::Ready(Some(PaStreamState::Ready)) => {
+                            if let Some(data) = data.take() {
+                                if let Err(e) = stream.write(&data).and_then(|_| stream.finish_upload()) {
+                                    return StdPoll::Ready(Err(e));
+                                }
+                            }
+                        },
+                        StdPoll::Ready(Some(PaStreamState::Terminated)) => return StdPoll::Ready(Ok(())),
+                        StdPoll::Ready(Some(PaStreamState::Failed)) => return StdPoll::Ready(Err(PaError::new(stream.get_context().errno()))),
+                        StdPoll::Ready(Some(_)) => (),
+                        StdPoll::Ready(None) => return StdPoll::Ready(Err(PaError::new(PA_ERR_INTERNAL as libc::c_int))),
+                        StdPoll::Pending => break,
+                    }
+                }
+                self.0 = UploadState::Uploading(stream, states, data);
+                StdPoll::Pending
+            },
+            UploadState::Failed(e) => StdPoll::Ready(Err(e)),
+            UploadState::Done => panic!("Polled a completed sample upload"),
+        }
+    }
+}
+
+impl<M: PaMainLoopApi> Future for PaSampleUploadFuture<M> {
+    type Item = ();
+    type Error = PaError;
+
+    fn poll(&mut self) -> Poll<(), PaError> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(res) => res.map(Async::Ready),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl<M: PaMainLoopApi> StdFuture for PaSampleUploadFuture<M> {
+    type Output = Result<(), PaError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Result<(), PaError>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
+
+impl<M: PaMainLoopApi> Unpin for PaSampleUploadFuture<M> {}
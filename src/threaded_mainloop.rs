@@ -0,0 +1,257 @@
+//! Wrapper for pulseaudio's threaded mainloop implementation.
+use libc;
+use libpulse_sys::*;
+use std::marker::PhantomData;
+use std::panic;
+use std::ptr::null_mut;
+use std::sync::{Arc, Mutex};
+
+use mainloop_api::PaMainLoopApi;
+use panic_guard;
+
+struct ThreadedMainLoopImpl {
+    raw: *mut pa_threaded_mainloop,
+    // Panics caught in callbacks in the event loop thread.
+    panic: panic_guard::PanicSlot,
+}
+
+// All functions of the threaded mainloop that we call without holding the lock
+// are thread safe.
+unsafe impl Send for ThreadedMainLoopImpl {}
+unsafe impl Sync for ThreadedMainLoopImpl {}
+
+impl ThreadedMainLoopImpl {
+    fn lock(&self) -> ThreadedMainLoopGuard {
+        assert!(!self.in_thread(), "The threaded mainloop must not be locked from its event loop thread");
+        unsafe { pa_threaded_mainloop_lock(self.raw) };
+        let guard = ThreadedMainLoopGuard { mainloop: self, _not_send: PhantomData };
+        guard.resume();
+        guard
+    }
+
+    fn in_thread(&self) -> bool {
+        unsafe { pa_threaded_mainloop_in_thread(self.raw) != 0 }
+    }
+
+    fn signal(&self, wait_for_accept: bool) {
+        unsafe { pa_threaded_mainloop_signal(self.raw, wait_for_accept as libc::c_int) }
+    }
+
+    fn submit<F>(&self, f: F) where F: FnOnce() + Send + 'static {
+        let f: Box<Box<FnOnce() + Send>> = Box::new(Box::new(f));
+        let api = unsafe { pa_threaded_mainloop_get_api(self.raw) };
+        let userdata = Box::into_raw(f) as *mut libc::c_void;
+        if self.in_thread() {
+            unsafe { pa_mainloop_api_once(api, Some(once_cb), userdata) };
+        } else {
+            let _guard = self.lock();
+            unsafe { pa_mainloop_api_once(api, Some(once_cb), userdata) };
+        }
+    }
+}
+
+impl Drop for ThreadedMainLoopImpl {
+    fn drop(&mut self) {
+        // Stopping the event loop thread from within itself aborts the process.
+        assert!(!self.in_thread(), "The last reference to a threaded mainloop must not be dropped from its event loop thread");
+        // This also stops the event loop thread.
+        unsafe { pa_threaded_mainloop_free(self.raw) };
+    }
+}
+
+/// Implementation of [`PaMainLoopApi`] using pulseaudio's `pa_threaded_mainloop`.
+///
+/// The mainloop runs in its own event loop thread. Callbacks and futures are always
+/// completed in the event loop thread, so the objects of this crate that are attached
+/// to this mainloop, like [`PaContext`], must be created in the event loop thread, using
+/// [`ThreadedMainLoopHandle::submit`]. Since they are not `Send`, they cannot be used
+/// from other threads afterwards. Other threads can use a
+/// [`ContextHandle`](../context/struct.ContextHandle.html) instead.
+///
+/// [`get_api`](../mainloop_api/trait.PaMainLoopApi.html#tymethod.get_api) panics if it
+/// is called outside of the event loop thread, so objects cannot be created elsewhere.
+/// The lock (see [`lock`](#method.lock)) only protects raw libpulse objects used by
+/// other threads.
+///
+/// The last reference to the mainloop must not be dropped from within the event loop
+/// thread, since this stops the thread. Doing so panics and leaks the mainloop.
+///
+/// Panics in callbacks and submitted closures in the event loop thread are re-raised
+/// by the next call to [`lock`](#method.lock) or
+/// [`ThreadedMainLoopHandle::run_locked`](struct.ThreadedMainLoopHandle.html#method.run_locked)
+/// in another thread.
+///
+/// [`PaMainLoopApi`]: ../mainloop_api/trait.PaMainLoopApi.html
+/// [`ThreadedMainLoopHandle::submit`]: struct.ThreadedMainLoopHandle.html#method.submit
+/// [`PaContext`]: ../context/struct.PaContext.html
+#[derive(Clone)]
+pub struct ThreadedMainLoop {
+    intern: Arc<ThreadedMainLoopImpl>,
+}
+
+impl ThreadedMainLoop {
+    /// Creates a new threaded mainloop.
+    ///
+    /// The event loop thread is not started until [`start`](#method.start) is called.
+    pub fn new() -> ThreadedMainLoop {
+        let raw = unsafe { pa_threaded_mainloop_new() };
+        assert!(raw != null_mut());
+        ThreadedMainLoop { intern: Arc::new(ThreadedMainLoopImpl { raw, panic: Arc::new(Mutex::new(None)) }) }
+    }
+
+    /// Starts the event loop thread.
+    pub fn start(&self) -> Result<(), ()> {
+        // Panics in the event loop thread are re-raised by the next thread that locks
+        // the mainloop.
+        let slot = self.intern.panic.clone();
+        self.intern.submit(move || panic_guard::set_thread_slot(slot));
+        if unsafe { pa_threaded_mainloop_start(self.intern.raw) } < 0 {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Terminates the event loop thread cleanly.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within the event loop thread.
+    pub fn stop(&self) {
+        assert!(!self.in_thread(), "The threaded mainloop must not be stopped from its event loop thread");
+        unsafe { pa_threaded_mainloop_stop(self.intern.raw) }
+    }
+
+    /// Locks the mainloop, blocking the event loop thread from processing events.
+    ///
+    /// The lock is recursive and is released when the returned guard is dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called from within the event loop thread, which always holds the lock
+    /// while dispatching events.
+    pub fn lock(&self) -> ThreadedMainLoopGuard {
+        self.intern.lock()
+    }
+
+    /// Signals all threads waiting in [`ThreadedMainLoopGuard::wait`].
+    ///
+    /// If `wait_for_accept` is true, blocks until one of the waiting threads has called
+    /// [`ThreadedMainLoopGuard::accept`]. This is usually called from a callback in the
+    /// event loop thread.
+    ///
+    /// [`ThreadedMainLoopGuard::wait`]: struct.ThreadedMainLoopGuard.html#method.wait
+    /// [`ThreadedMainLoopGuard::accept`]: struct.ThreadedMainLoopGuard.html#method.accept
+    pub fn signal(&self, wait_for_accept: bool) {
+        self.intern.signal(wait_for_accept)
+    }
+
+    /// Returns true if called from within the event loop thread.
+    pub fn in_thread(&self) -> bool {
+        self.intern.in_thread()
+    }
+
+    /// Returns the return value passed to the mainloop's quit callback.
+    pub fn get_retval(&self) -> libc::c_int {
+        unsafe { pa_threaded_mainloop_get_retval(self.intern.raw) }
+    }
+
+    /// Returns a handle that can be sent to other threads.
+    pub fn handle(&self) -> ThreadedMainLoopHandle {
+        ThreadedMainLoopHandle { intern: self.intern.clone() }
+    }
+}
+
+// The objects of this crate are not thread safe, and libpulse runs their callbacks in
+// the event loop thread.
+unsafe impl PaMainLoopApi for ThreadedMainLoop {
+    fn get_api(&self) -> *mut pa_mainloop_api {
+        assert!(self.in_thread(), "Objects attached to a threaded mainloop must be created in its event loop thread");
+        unsafe { pa_threaded_mainloop_get_api(self.intern.raw) }
+    }
+}
+
+/// A `Send` handle to a [`ThreadedMainLoop`](struct.ThreadedMainLoop.html).
+///
+/// The handle keeps the mainloop alive.
+#[derive(Clone)]
+pub struct ThreadedMainLoopHandle {
+    intern: Arc<ThreadedMainLoopImpl>,
+}
+
+impl ThreadedMainLoopHandle {
+    /// Locks the mainloop. See [`ThreadedMainLoop::lock`](struct.ThreadedMainLoop.html#method.lock).
+    pub fn lock(&self) -> ThreadedMainLoopGuard {
+        self.intern.lock()
+    }
+
+    /// Signals all threads waiting for the mainloop.
+    /// See [`ThreadedMainLoop::signal`](struct.ThreadedMainLoop.html#method.signal).
+    pub fn signal(&self, wait_for_accept: bool) {
+        self.intern.signal(wait_for_accept)
+    }
+
+    /// Runs `f` while holding the mainloop lock.
+    pub fn run_locked<F, R>(&self, f: F) -> R where F: FnOnce() -> R {
+        let guard = self.lock();
+        let r = f();
+        // Callbacks may have run while `f` was waiting.
+        guard.resume();
+        r
+    }
+
+    /// Runs `f` once in the event loop thread.
+    ///
+    /// This does not block until `f` has run. It may be called from any thread,
+    /// including the event loop thread itself.
+    pub fn submit<F>(&self, f: F) where F: FnOnce() + Send + 'static {
+        self.intern.submit(f)
+    }
+}
+
+/// Guard for the lock of a [`ThreadedMainLoop`](struct.ThreadedMainLoop.html).
+///
+/// The lock is released when the guard is dropped.
+pub struct ThreadedMainLoopGuard<'a> {
+    mainloop: &'a ThreadedMainLoopImpl,
+    // The lock must be released by the thread that acquired it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a> ThreadedMainLoopGuard<'a> {
+    // Re-raises a panic from the event loop thread, releasing the lock.
+    fn resume(&self) {
+        let payload = self.mainloop.panic.lock().unwrap().take();
+        if let Some(payload) = payload {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Releases the lock and waits until the event loop thread calls
+    /// [`ThreadedMainLoop::signal`](struct.ThreadedMainLoop.html#method.signal).
+    ///
+    /// The lock is held again when this function returns. Spurious wakeups are possible,
+    /// so the condition that is waited for should be checked in a loop.
+    pub fn wait(&self) {
+        unsafe { pa_threaded_mainloop_wait(self.mainloop.raw) }
+    }
+
+    /// Accepts a signal from the event loop thread that was sent with `wait_for_accept`.
+    pub fn accept(&self) {
+        unsafe { pa_threaded_mainloop_accept(self.mainloop.raw) }
+    }
+}
+
+impl<'a> Drop for ThreadedMainLoopGuard<'a> {
+    fn drop(&mut self) {
+        unsafe { pa_threaded_mainloop_unlock(self.mainloop.raw) }
+    }
+}
+
+extern "C" fn once_cb(_api: *mut pa_mainloop_api, userdata: *mut libc::c_void) {
+    panic_guard::guard((), || {
+        assert!(userdata != null_mut());
+        let f = unsafe { Box::from_raw(userdata as *mut Box<FnOnce() + Send>) };
+        f();
+    })
+}
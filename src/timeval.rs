@@ -0,0 +1,100 @@
+//! Conversion of the `timeval`s passed to pulseaudio time events.
+//!
+//! libpulse passes either wall-clock times or, for timers created with
+//! `pa_context_rttime_new`, times of the monotonic clock. The latter are marked by
+//! setting `PA_TIMEVAL_RTCLOCK` in `tv_usec`. Both are converted to an `Instant`, so
+//! that timers are not affected by wall-clock jumps once they are scheduled.
+use libc;
+use std::mem;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+// Defined as a macro in pulse/timeval.h, so it is missing from libpulse-sys.
+const PA_TIMEVAL_RTCLOCK: libc::suseconds_t = 1 << 30;
+
+// Used for deadlines that cannot be represented by an `Instant`.
+const FAR_FUTURE: Duration = Duration::from_secs(86400 * 365 * 30);
+
+/// Returns the point in time at which a time event for `tv` expires.
+///
+/// Times in the past yield the current time, times too far in the future for an
+/// `Instant` yield a point about 30 years from now.
+pub fn deadline(tv: &libc::timeval) -> Instant {
+    let now = Instant::now();
+    now.checked_add(remaining(tv)).unwrap_or_else(|| now + FAR_FUTURE)
+}
+
+/// Returns the time until a time event for `tv` expires, or zero if it is in the past.
+///
+/// Negative seconds count as zero, microseconds out of range are clamped.
+pub fn remaining(tv: &libc::timeval) -> Duration {
+    let rtclock = tv.tv_usec & PA_TIMEVAL_RTCLOCK != 0;
+    let usec = (tv.tv_usec & !PA_TIMEVAL_RTCLOCK).max(0).min(999999);
+    let t = Duration::new(tv.tv_sec.max(0) as u64, usec as u32 * 1000);
+    let now = if rtclock {
+        monotonic_now()
+    } else {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(Duration::new(0, 0))
+    };
+    t.checked_sub(now).unwrap_or(Duration::new(0, 0))
+}
+
+// The clock libpulse uses for pa_rtclock_now().
+fn monotonic_now() -> Duration {
+    let mut ts: libc::timespec = unsafe { mem::zeroed() };
+    let r = unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut ts) };
+    assert!(r == 0);
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use libc;
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+    use super::{deadline, monotonic_now, remaining, PA_TIMEVAL_RTCLOCK};
+
+    fn timeval(t: Duration) -> libc::timeval {
+        libc::timeval { tv_sec: t.as_secs() as libc::time_t, tv_usec: t.subsec_micros() as libc::suseconds_t }
+    }
+
+    fn assert_about(actual: Duration, expected: Duration) {
+        assert!(actual <= expected && actual + Duration::from_secs(1) > expected, "{:?} is not about {:?}", actual, expected);
+    }
+
+    #[test]
+    fn wall_clock() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        assert_about(remaining(&timeval(now + Duration::from_secs(10))), Duration::from_secs(10));
+        assert_eq!(remaining(&timeval(now - Duration::from_secs(10))), Duration::new(0, 0));
+    }
+
+    #[test]
+    fn rtclock() {
+        let mut tv = timeval(monotonic_now() + Duration::from_secs(10));
+        tv.tv_usec |= PA_TIMEVAL_RTCLOCK;
+        assert_about(remaining(&tv), Duration::from_secs(10));
+
+        // Without the flag, the monotonic time is far in the past of the wall clock.
+        tv.tv_usec &= !PA_TIMEVAL_RTCLOCK;
+        assert_eq!(remaining(&tv), Duration::new(0, 0));
+    }
+
+    #[test]
+    fn malformed() {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+        let mut tv = timeval(now + Duration::from_secs(10));
+        tv.tv_usec = 5000000;
+        let r = remaining(&tv);
+        assert!(r > Duration::from_secs(9) && r < Duration::from_secs(11));
+        tv.tv_usec = -1;
+        let r = remaining(&tv);
+        assert!(r > Duration::from_secs(9) && r <= Duration::from_secs(10));
+        tv.tv_sec = -1;
+        assert_eq!(remaining(&tv), Duration::new(0, 0));
+    }
+
+    #[test]
+    fn far_future() {
+        let tv = libc::timeval { tv_sec: libc::time_t::max_value(), tv_usec: 0 };
+        assert!(deadline(&tv) > Instant::now() + Duration::from_secs(86400 * 365));
+    }
+}
@@ -0,0 +1,143 @@
+use libc;
+use libpulse_sys::*;
+use std::cell::{Cell, RefCell};
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+use tokio::task;
+
+use api_vtable::{self, EventKind};
+use super::Tokio1MainLoopApiImpl;
+
+struct DeferredEvent {
+    parent: Weak<Tokio1MainLoopApiImpl>,
+    dead: Cell<bool>,
+    active: Cell<bool>,
+    cb: pa_defer_event_cb_t,
+    destroy_cb: Cell<pa_defer_event_destroy_cb_t>,
+    userdata: *mut libc::c_void,
+}
+
+impl DeferredEvent {
+    fn as_ptr(&self) -> *mut pa_defer_event {
+        self as *const _ as *mut _
+    }
+
+    fn call_destroy_cb(&self, api: *mut pa_mainloop_api) {
+        if let Some(cb) = self.destroy_cb.take() {
+            unsafe { cb(api, self.as_ptr(), self.userdata) };
+        }
+    }
+}
+
+pub struct Deferred {
+    events: RefCell<Vec<Rc<DeferredEvent>>>,
+    waker: Cell<Option<Waker>>,
+}
+
+impl Deferred {
+    pub fn new() -> Deferred {
+        Deferred { events: RefCell::new(Vec::new()), waker: Cell::new(None) }
+    }
+
+    pub fn free_all(&self, api: *mut pa_mainloop_api) {
+        let events = mem::replace(&mut *self.events.borrow_mut(), Vec::new());
+        events.into_iter().for_each(|ev| {
+            ev.dead.set(true);
+            ev.call_destroy_cb(api);
+        });
+        self.wake();
+    }
+
+    pub fn launch(data: &Tokio1MainLoopApiImpl) {
+        task::spawn_local(DeferredEventFuture(data.weak_ref()));
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    pub fn add(&self, data: &Tokio1MainLoopApiImpl, cb: pa_defer_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_defer_event {
+        let ev = Rc::new(DeferredEvent {
+            parent: data.weak_ref(),
+            dead: Cell::new(false),
+            active: Cell::new(true),
+            cb,
+            destroy_cb: Cell::new(None),
+            userdata,
+        });
+        let ptr = ev.as_ptr();
+        self.events.borrow_mut().push(ev);
+        self.wake();
+        ptr
+    }
+
+    unsafe fn event_fn<F>(e: *mut pa_defer_event, f: F) where F: FnOnce(&Deferred, &DeferredEvent) {
+        let ev = &*(e as *const DeferredEvent);
+        if let Some(p) = ev.parent.upgrade() {
+            f(&p.deferred, ev);
+        }
+    }
+
+    pub unsafe fn enable(e: *mut pa_defer_event, enabled: bool) {
+        Self::event_fn(e, |data, ev| {
+            if !ev.active.replace(enabled) && enabled {
+                data.wake();
+            }
+        })
+    }
+
+    pub unsafe fn free(e: *mut pa_defer_event) {
+        Self::event_fn(e, |data, ev| {
+            // The destroy callback is called from the deferred event task, since
+            // the event may be freed from within its own callback.
+            ev.active.set(false);
+            ev.dead.set(true);
+            data.wake();
+        })
+    }
+
+    pub unsafe fn set_destroy(e: *mut pa_defer_event, cb: pa_defer_event_destroy_cb_t) {
+        (*(e as *const DeferredEvent)).destroy_cb.set(cb);
+    }
+}
+
+struct DeferredEventFuture(Weak<Tokio1MainLoopApiImpl>);
+
+impl Future for DeferredEventFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let data = match self.0.upgrade() {
+            Some(data) => data,
+            None => return Poll::Ready(()),
+        };
+        data.deferred.waker.set(Some(cx.waker().clone()));
+
+        let events = data.deferred.events.borrow().clone();
+        for ev in events.iter() {
+            if ev.active.get() {
+                api_vtable::dispatch(EventKind::Defer, || unsafe { ev.cb.unwrap()(data.get_api(), ev.as_ptr(), ev.userdata) });
+            }
+        }
+        drop(events);
+
+        let dead_events: Vec<Rc<DeferredEvent>>;
+        {
+            let mut events = data.deferred.events.borrow_mut();
+            dead_events = events.iter().filter(|ev| ev.dead.get()).cloned().collect();
+            events.retain(|ev| !ev.dead.get());
+        }
+        dead_events.into_iter().for_each(|ev| ev.call_destroy_cb(data.get_api()));
+
+        if data.deferred.events.borrow().iter().any(|ev| ev.active.get()) {
+            // Yield to the executor before dispatching the next round.
+            cx.waker().wake_by_ref();
+        }
+        Poll::Pending
+    }
+}
@@ -0,0 +1,239 @@
+use libc;
+use libpulse_sys::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::io;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+use tokio::task;
+
+use api_vtable::{self, EventKind};
+use super::Tokio1MainLoopApiImpl;
+
+struct IoEvent {
+    parent: Weak<Tokio1MainLoopApiImpl>,
+    fd: RawFd,
+    events: Cell<pa_io_event_flags_t>,
+    dead: Cell<bool>,
+    cb: pa_io_event_cb_t,
+    destroy_cb: Cell<pa_io_event_destroy_cb_t>,
+    userdata: *mut libc::c_void,
+    waker: Cell<Option<Waker>>,
+}
+
+impl IoEvent {
+    fn as_ptr(&self) -> *mut pa_io_event {
+        self as *const _ as *mut _
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn call_destroy_cb(&self, api: *mut pa_mainloop_api) {
+        if let Some(cb) = self.destroy_cb.take() {
+            unsafe { cb(api, self.as_ptr(), self.userdata) };
+        }
+    }
+}
+
+pub struct Io(RefCell<HashMap<usize, Rc<IoEvent>>>);
+
+impl Io {
+    pub fn new() -> Io {
+        Io(RefCell::new(HashMap::new()))
+    }
+
+    pub fn spawn(&self,
+                 fd: RawFd,
+                 events: pa_io_event_flags_t,
+                 cb: pa_io_event_cb_t,
+                 userdata: *mut libc::c_void,
+                 data: &Tokio1MainLoopApiImpl) -> *mut pa_io_event {
+        let ev = Rc::new(IoEvent {
+            parent: data.weak_ref(),
+            fd,
+            events: Cell::new(events),
+            dead: Cell::new(false),
+            cb,
+            destroy_cb: Cell::new(None),
+            userdata,
+            waker: Cell::new(None),
+        });
+        let ptr = ev.as_ptr();
+        let async_fd = AsyncFd::with_interest(fd, Interest::READABLE | Interest::WRITABLE);
+        #[cfg(feature = "tracing")]
+        {
+            if let Err(ref e) = async_fd {
+                ::tracing::warn!(fd, error = %e, "unable to register file descriptor");
+            }
+        }
+        task::spawn_local(IoEventFuture { ev: ev.clone(), fd: async_fd });
+        self.0.borrow_mut().insert(ptr as usize, ev);
+        ptr
+    }
+
+    unsafe fn event<'a>(e: *mut pa_io_event) -> &'a IoEvent {
+        &*(e as *const IoEvent)
+    }
+
+    pub unsafe fn enable(e: *mut pa_io_event,
+                         events: pa_io_event_flags_t) {
+        let ev = Self::event(e);
+        ev.events.set(events);
+        ev.wake();
+    }
+
+    pub unsafe fn free(e: *mut pa_io_event) {
+        let ev = Self::event(e);
+        ev.dead.set(true);
+        ev.wake();
+        if let Some(p) = ev.parent.upgrade() {
+            // The event task holds another reference until it has called the destroy callback.
+            drop(p.io.0.borrow_mut().remove(&(e as usize)));
+        }
+    }
+
+    pub unsafe fn set_destroy_cb(e: *mut pa_io_event,
+                                 cb: pa_io_event_destroy_cb_t) {
+        Self::event(e).destroy_cb.set(cb);
+    }
+
+    pub fn free_all(&self, api: *mut pa_mainloop_api) {
+        let events = mem::replace(&mut *self.0.borrow_mut(), HashMap::new());
+        events.into_iter().for_each(|(_, ev)| {
+            ev.dead.set(true);
+            ev.call_destroy_cb(api);
+            ev.wake();
+        });
+    }
+}
+
+fn pulse_to_poll(flags: pa_io_event_flags_t) -> libc::c_short {
+    let mut ret = 0;
+    if flags & PA_IO_EVENT_INPUT != 0 {
+        ret |= libc::POLLIN;
+    }
+    if flags & PA_IO_EVENT_OUTPUT != 0 {
+        ret |= libc::POLLOUT;
+    }
+    ret
+}
+
+fn poll_to_pulse(flags: libc::c_short) -> pa_io_event_flags_t {
+    let mut ret = PA_IO_EVENT_NULL;
+    if flags & libc::POLLIN != 0 {
+        ret |= PA_IO_EVENT_INPUT;
+    }
+    if flags & libc::POLLOUT != 0 {
+        ret |= PA_IO_EVENT_OUTPUT;
+    }
+    if flags & libc::POLLHUP != 0 {
+        ret |= PA_IO_EVENT_HANGUP;
+    }
+    if flags & (libc::POLLERR | libc::POLLNVAL) != 0 {
+        ret |= PA_IO_EVENT_ERROR;
+    }
+    ret
+}
+
+/// Checks the actual readiness of the file descriptor.
+///
+/// `AsyncFd` readiness is edge triggered, while pulseaudio expects level triggered
+/// io events.
+fn poll_fd(fd: RawFd, events: pa_io_event_flags_t) -> pa_io_event_flags_t {
+    let mut pollfd = libc::pollfd { fd, events: pulse_to_poll(events), revents: 0 };
+    loop {
+        match unsafe { libc::poll(&mut pollfd, 1, 0) } {
+            -1 if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => continue,
+            -1 => return PA_IO_EVENT_ERROR,
+            0 => return PA_IO_EVENT_NULL,
+            _ => return poll_to_pulse(pollfd.revents),
+        }
+    }
+}
+
+struct IoEventFuture {
+    ev: Rc<IoEvent>,
+    fd: io::Result<AsyncFd<RawFd>>,
+}
+
+impl Future for IoEventFuture {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = &*self;
+        loop {
+            let parent = match this.ev.parent.upgrade() {
+                Some(p) => p,
+                None => return Poll::Ready(()),
+            };
+            if this.ev.dead.get() {
+                this.ev.call_destroy_cb(parent.get_api());
+                return Poll::Ready(());
+            }
+            this.ev.waker.set(Some(cx.waker().clone()));
+
+            let events = this.ev.events.get();
+            if events == PA_IO_EVENT_NULL {
+                return Poll::Pending;
+            }
+
+            let ready = match this.fd {
+                Ok(ref fd) => {
+                    let ready = poll_fd(this.ev.fd, events);
+                    if ready == PA_IO_EVENT_NULL {
+                        let mut retry = false;
+                        if events & PA_IO_EVENT_INPUT != 0 {
+                            match fd.poll_read_ready(cx) {
+                                Poll::Ready(Ok(mut guard)) => { guard.clear_ready(); retry = true; },
+                                Poll::Ready(Err(e)) => return this.poll_error(&parent, e, cx),
+                                Poll::Pending => (),
+                            }
+                        }
+                        if events & PA_IO_EVENT_OUTPUT != 0 {
+                            match fd.poll_write_ready(cx) {
+                                Poll::Ready(Ok(mut guard)) => { guard.clear_ready(); retry = true; },
+                                Poll::Ready(Err(e)) => return this.poll_error(&parent, e, cx),
+                                Poll::Pending => (),
+                            }
+                        }
+                        if retry {
+                            continue;
+                        }
+                        return Poll::Pending;
+                    }
+                    ready
+                },
+                // The file descriptor could not be registered with the reactor.
+                Err(_) => PA_IO_EVENT_HANGUP | PA_IO_EVENT_ERROR,
+            };
+            return this.dispatch(&parent, ready, cx);
+        }
+    }
+}
+
+impl IoEventFuture {
+    fn poll_error(&self, parent: &Tokio1MainLoopApiImpl, e: io::Error, cx: &mut Context) -> Poll<()> {
+        #[cfg(feature = "tracing")]
+        ::tracing::warn!(fd = self.ev.fd, error = %e, "unable to poll file descriptor");
+        #[cfg(not(feature = "tracing"))]
+        let _ = e;
+        self.dispatch(parent, PA_IO_EVENT_ERROR, cx)
+    }
+
+    fn dispatch(&self, parent: &Tokio1MainLoopApiImpl, events: pa_io_event_flags_t, cx: &mut Context) -> Poll<()> {
+        api_vtable::dispatch(EventKind::Io, || unsafe { self.ev.cb.unwrap()(parent.get_api(), self.ev.as_ptr(), self.ev.fd, events, self.ev.userdata) });
+        // Level triggered: check the file descriptor again after yielding to the executor.
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
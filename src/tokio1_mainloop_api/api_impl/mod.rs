@@ -0,0 +1,122 @@
+mod deferred;
+mod io;
+mod timer;
+
+use libc;
+use libpulse_sys::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::rc::Weak;
+use api_vtable::{self, MainLoopBackend};
+use channel::BroadcastSender;
+use mainloop_api::PaQuitRequestStream;
+use self::timer::Timers;
+use self::deferred::Deferred;
+use self::io::Io;
+
+pub struct Tokio1MainLoopApiImpl {
+    weak_self_ref: RefCell<Option<Weak<Tokio1MainLoopApiImpl>>>,
+    api: pa_mainloop_api,
+    timers: Timers,
+    deferred: Deferred,
+    io: Io,
+    quit: BroadcastSender<libc::c_int>,
+}
+
+impl Tokio1MainLoopApiImpl {
+    pub fn get_api(&self) -> *mut pa_mainloop_api {
+        &self.api as *const _ as *mut _
+    }
+
+    pub fn quit_requests(&self) -> PaQuitRequestStream {
+        PaQuitRequestStream::new(self.quit.subscribe())
+    }
+
+    fn weak_ref(&self) -> Weak<Tokio1MainLoopApiImpl> {
+        self.weak_self_ref.borrow().as_ref().unwrap().clone()
+    }
+}
+
+impl Drop for Tokio1MainLoopApiImpl {
+    fn drop(&mut self) {
+        let api = self.get_api();
+        self.deferred.free_all(api);
+        self.timers.free_all(api);
+        self.io.free_all(api);
+    }
+}
+
+pub fn new() -> Rc<Tokio1MainLoopApiImpl> {
+    let mut intern = Rc::new(Tokio1MainLoopApiImpl {
+        api: api_vtable::vtable::<Tokio1MainLoopApiImpl>(),
+        weak_self_ref: RefCell::new(None),
+        timers: Timers::new(),
+        deferred: Deferred::new(),
+        io: Io::new(),
+        quit: BroadcastSender::new(),
+    });
+
+    {
+        let intern_mut = Rc::get_mut(&mut intern).unwrap();
+        let ptr = intern_mut as *mut Tokio1MainLoopApiImpl as *mut libc::c_void;
+        intern_mut.api.userdata = ptr;
+    }
+    let intern_weak = Rc::downgrade(&intern);
+    *intern.weak_self_ref.borrow_mut() = Some(intern_weak);
+    Deferred::launch(&*intern);
+    intern
+}
+
+impl MainLoopBackend for Tokio1MainLoopApiImpl {
+    fn io_new(&self, fd: libc::c_int, events: pa_io_event_flags_t, cb: pa_io_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_io_event {
+        self.io.spawn(fd, events, cb, userdata, self)
+    }
+
+    unsafe fn io_enable(e: *mut pa_io_event, events: pa_io_event_flags_t) {
+        Io::enable(e, events)
+    }
+
+    unsafe fn io_free(e: *mut pa_io_event) {
+        Io::free(e)
+    }
+
+    unsafe fn io_set_destroy(e: *mut pa_io_event, cb: pa_io_event_destroy_cb_t) {
+        Io::set_destroy_cb(e, cb)
+    }
+
+    fn time_new(&self, tv: Option<&libc::timeval>, cb: pa_time_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_time_event {
+        self.timers.spawn(tv, cb, userdata, self)
+    }
+
+    unsafe fn time_restart(e: *mut pa_time_event, tv: Option<&libc::timeval>) {
+        Timers::restart(e, tv)
+    }
+
+    unsafe fn time_free(e: *mut pa_time_event) {
+        Timers::free(e)
+    }
+
+    unsafe fn time_set_destroy(e: *mut pa_time_event, cb: pa_time_event_destroy_cb_t) {
+        Timers::set_destroy_cb(e, cb)
+    }
+
+    fn defer_new(&self, cb: pa_defer_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_defer_event {
+        self.deferred.add(self, cb, userdata)
+    }
+
+    unsafe fn defer_enable(e: *mut pa_defer_event, enabled: bool) {
+        Deferred::enable(e, enabled)
+    }
+
+    unsafe fn defer_free(e: *mut pa_defer_event) {
+        Deferred::free(e)
+    }
+
+    unsafe fn defer_set_destroy(e: *mut pa_defer_event, cb: pa_defer_event_destroy_cb_t) {
+        Deferred::set_destroy(e, cb)
+    }
+
+    fn quit(&self, retval: libc::c_int) {
+        self.quit.send(retval)
+    }
+}
@@ -0,0 +1,153 @@
+use libc;
+use libpulse_sys::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::future::Future;
+use std::mem;
+use std::pin::Pin;
+use std::rc::{Rc, Weak};
+use std::task::{Context, Poll, Waker};
+use tokio::task;
+use tokio::time::{self, Sleep};
+
+use timeval::deadline;
+
+use api_vtable::{self, EventKind};
+use super::Tokio1MainLoopApiImpl;
+
+struct TimeEvent {
+    parent: Weak<Tokio1MainLoopApiImpl>,
+    tv: Cell<Option<libc::timeval>>,
+    dead: Cell<bool>,
+    cb: pa_time_event_cb_t,
+    destroy_cb: Cell<pa_time_event_destroy_cb_t>,
+    userdata: *mut libc::c_void,
+    waker: Cell<Option<Waker>>,
+}
+
+impl TimeEvent {
+    fn as_ptr(&self) -> *mut pa_time_event {
+        self as *const _ as *mut _
+    }
+
+    fn wake(&self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+
+    fn call_destroy_cb(&self, api: *mut pa_mainloop_api) {
+        if let Some(cb) = self.destroy_cb.take() {
+            unsafe { cb(api, self.as_ptr(), self.userdata) };
+        }
+    }
+}
+
+pub struct Timers(RefCell<HashMap<usize, Rc<TimeEvent>>>);
+
+impl Timers {
+    pub fn new() -> Timers {
+        Timers(RefCell::new(HashMap::new()))
+    }
+
+    pub fn spawn(&self,
+                 tv: Option<&libc::timeval>,
+                 cb: pa_time_event_cb_t,
+                 userdata: *mut libc::c_void,
+                 data: &Tokio1MainLoopApiImpl) -> *mut pa_time_event {
+        let ev = Rc::new(TimeEvent {
+            parent: data.weak_ref(),
+            tv: Cell::new(tv.cloned()),
+            dead: Cell::new(false),
+            cb,
+            destroy_cb: Cell::new(None),
+            userdata,
+            waker: Cell::new(None),
+        });
+        let ptr = ev.as_ptr();
+        task::spawn_local(TimeEventFuture { ev: ev.clone(), sleep: None });
+        self.0.borrow_mut().insert(ptr as usize, ev);
+        ptr
+    }
+
+    unsafe fn event<'a>(e: *mut pa_time_event) -> &'a TimeEvent {
+        &*(e as *const TimeEvent)
+    }
+
+    pub unsafe fn restart(e: *mut pa_time_event, tv: Option<&libc::timeval>) {
+        let ev = Self::event(e);
+        ev.tv.set(tv.cloned());
+        ev.wake();
+    }
+
+    pub unsafe fn free(e: *mut pa_time_event) {
+        let ev = Self::event(e);
+        ev.dead.set(true);
+        ev.wake();
+        if let Some(p) = ev.parent.upgrade() {
+            // The event task holds another reference until it has called the destroy callback.
+            drop(p.timers.0.borrow_mut().remove(&(e as usize)));
+        }
+    }
+
+    pub unsafe fn set_destroy_cb(e: *mut pa_time_event,
+                                 cb: pa_time_event_destroy_cb_t) {
+        Self::event(e).destroy_cb.set(cb);
+    }
+
+    pub fn free_all(&self, api: *mut pa_mainloop_api) {
+        let events = mem::replace(&mut *self.0.borrow_mut(), HashMap::new());
+        events.into_iter().for_each(|(_, ev)| {
+            ev.dead.set(true);
+            ev.call_destroy_cb(api);
+            ev.wake();
+        });
+    }
+}
+
+struct TimeEventFuture {
+    ev: Rc<TimeEvent>,
+    sleep: Option<(libc::timeval, Pin<Box<Sleep>>)>,
+}
+
+impl Future for TimeEventFuture {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<()> {
+        let this = &mut *self;
+        loop {
+            let parent = match this.ev.parent.upgrade() {
+                Some(p) => p,
+                None => return Poll::Ready(()),
+            };
+            if this.ev.dead.get() {
+                this.ev.call_destroy_cb(parent.get_api());
+                return Poll::Ready(());
+            }
+            this.ev.waker.set(Some(cx.waker().clone()));
+
+            let tv = match this.ev.tv.get() {
+                Some(tv) => tv,
+                None => {
+                    this.sleep = None;
+                    return Poll::Pending;
+                }
+            };
+            let restarted = match this.sleep {
+                Some((old, _)) => old.tv_sec != tv.tv_sec || old.tv_usec != tv.tv_usec,
+                None => true,
+            };
+            if restarted {
+                this.sleep = Some((tv, Box::pin(time::sleep_until(deadline(&tv).into()))));
+            }
+            if this.sleep.as_mut().unwrap().1.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+
+            // Time events fire only once, the callback may restart them.
+            this.sleep = None;
+            this.ev.tv.set(None);
+            api_vtable::dispatch(EventKind::Time, || unsafe { this.ev.cb.unwrap()(parent.get_api(), this.ev.as_ptr(), &tv, this.ev.userdata) });
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! Implementation of [`PaMainLoopApi`] for tokio 1.x.
+//!
+//! This module is only available if the `tokio` feature is enabled.
+//!
+//! [`PaMainLoopApi`]: ../mainloop_api/trait.PaMainLoopApi.html
+mod api_impl;
+
+use libpulse_sys::*;
+use std::rc::Rc;
+
+use self::api_impl::Tokio1MainLoopApiImpl;
+use super::mainloop_api::{PaMainLoopApi, PaQuitRequestStream};
+
+/// Implementation of [`PaMainLoopApi`] for tokio 1.x.
+///
+/// All pulseaudio events are driven by tasks spawned with
+/// `tokio::task::spawn_local`, so the mainloop API must be created
+/// and used from within a `tokio::task::LocalSet`. Io events use
+/// `tokio::io::unix::AsyncFd`, time events use `tokio::time`.
+///
+/// Unlike [`PaMainLoopApiTokio`], enabled deferred events are dispatched
+/// once per poll of the deferred event task, which then yields to the
+/// executor.
+///
+/// Panics in callbacks do not unwind through the internal tasks, which keep
+/// dispatching events. They are re-raised when a future or stream of this crate,
+/// e.g. [`quit_requests`](#method.quit_requests), is polled next on the same thread.
+///
+/// [`PaMainLoopApi`]: ../mainloop_api/trait.PaMainLoopApi.html
+/// [`PaMainLoopApiTokio`]: ../tokio_mainloop_api/struct.PaMainLoopApiTokio.html
+#[derive(Clone)]
+pub struct PaMainLoopApiTokio1 {
+    intern: Rc<Tokio1MainLoopApiImpl>,
+}
+
+impl PaMainLoopApiTokio1 {
+    /// Creates a new pulseaudio mainloop API.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a `tokio::task::LocalSet`.
+    pub fn new() -> PaMainLoopApiTokio1 {
+        PaMainLoopApiTokio1 { intern: api_impl::new() }
+    }
+
+    /// Returns a stream of requests to quit the mainloop.
+    ///
+    /// Applications should shut down when a request arrives. See
+    /// [`PaQuitRequestStream`](../mainloop_api/struct.PaQuitRequestStream.html).
+    pub fn quit_requests(&self) -> PaQuitRequestStream {
+        self.intern.quit_requests()
+    }
+}
+
+unsafe impl PaMainLoopApi for PaMainLoopApiTokio1 {
+    fn get_api(&self) -> *mut pa_mainloop_api {
+        self.intern.get_api()
+    }
+}
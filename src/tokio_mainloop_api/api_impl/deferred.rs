@@ -9,6 +9,8 @@ use std::rc::Weak;
 use std::mem;
 use std::ops::DerefMut;
 
+use api_vtable::EventKind;
+
 use super::TokioMainLoopApiImpl;
 use super::external_reference::ExternalReference;
 
@@ -25,11 +27,18 @@ pub struct Deferred {
     events: HashMap<usize, DeferredEvent>,
     new_events: RefCell<HashMap<usize, DeferredEvent>>,
     task: Option<Task>,
+    // Maximum number of dispatch rounds per poll, unlimited if None.
+    budget: Option<u32>,
 }
 
 impl Deferred {
-    pub fn new() -> Deferred {
-        Deferred{ events: HashMap::new(), new_events: RefCell::new(HashMap::new()), task: None }
+    pub fn new(budget: Option<u32>) -> Deferred {
+        Deferred{ events: HashMap::new(), new_events: RefCell::new(HashMap::new()), task: None, budget }
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn count(&self) -> usize {
+        self.events.len() + self.new_events.borrow().len()
     }
 
     pub fn free_all(&mut self, api: *mut pa_mainloop_api) {
@@ -155,14 +164,24 @@ impl Future for DeferredEventFuture {
                 }
             }
 
+            let budget = data.deferred.borrow().budget;
+            let mut rounds = 0;
             loop {
+                if budget.map_or(false, |b| rounds >= b) {
+                    // Yield to the reactor, but make sure we are polled again.
+                    task::current().notify();
+                    data.stats.deferred_poll(rounds);
+                    return Ok(Async::NotReady);
+                }
+                rounds += 1;
+
                 let mut abort = true;
 
                 {
                     let d = data.deferred.borrow();
                     for v in d.events.values() {
                         if v.active.get() {
-                            unsafe { v.cb.unwrap()(data.get_api(), v.reference.as_ptr(), v.userdata) };
+                            data.dispatch(EventKind::Defer, || unsafe { v.cb.unwrap()(data.get_api(), v.reference.as_ptr(), v.userdata) });
                             abort = false;
                         }
                     }
@@ -206,6 +225,7 @@ impl Future for DeferredEventFuture {
                 }
 
                 if abort {
+                    data.stats.deferred_poll(rounds);
                     return Ok(Async::NotReady);
                 }
             }
@@ -70,5 +70,14 @@ pub fn poll_to_mio(flags: libc::c_short) -> Ready {
     if flags & libc::POLLERR != 0 {
         ret |= UnixReady::error();
     }
+    if flags & libc::POLLNVAL != 0 {
+        // The file descriptor was closed.
+        ret |= error_flags();
+    }
     ret
 }
+
+/// Flags that are reported even if they were not requested, just like poll() does.
+pub fn error_flags() -> Ready {
+    (UnixReady::hup() | UnixReady::error()).into()
+}
@@ -12,7 +12,9 @@ use std::rc::{Rc, Weak};
 use self::poll_evented_level::PollEventedFdLevel;
 use super::flags;
 use super::super::TokioMainLoopApiImpl;
+use ::api_vtable::EventKind;
 use ::explicit_cleanup::ExplicitCleanup;
+use ::tokio_mainloop_api::PaIoError;
 
 struct IoEventStreamData {
     task: Option<Task>,
@@ -21,8 +23,11 @@ struct IoEventStreamData {
 
 struct IoEventStream {
     parent: Weak<TokioMainLoopApiImpl>,
-    poll: ExplicitCleanup<PollEventedFdLevel>,
+    fd: RawFd,
+    // None if the file descriptor could not be registered with the reactor.
+    poll: ExplicitCleanup<Option<PollEventedFdLevel>>,
     data: ExplicitCleanup<Rc<RefCell<IoEventStreamData>>>,
+    failed: bool,
 }
 
 pub struct IoEventStreams(RefCell<HashMap<RawFd, Weak<RefCell<IoEventStreamData>>>>);
@@ -38,10 +43,21 @@ impl IoEventStreams {
         let data = Rc::new(RefCell::new(IoEventStreamData { task: None, events: event_map }));
         let ret = Rc::downgrade(&data);
         let weak = parent.weak_ref(); 
+        let poll = match PollEventedFdLevel::new(fd, &parent.handle) {
+            Ok(poll) => Some(poll),
+            Err(e) => {
+                #[cfg(feature = "tracing")]
+                ::tracing::warn!(fd, error = %e, "unable to register file descriptor");
+                parent.io_errors.send(PaIoError::new(fd, e));
+                None
+            },
+        };
         parent.handle.spawn(IoEventStream {
             parent: parent.weak_ref(),
-            poll: ExplicitCleanup::new(PollEventedFdLevel::new(fd, &parent.handle).unwrap()),
+            fd,
+            poll: ExplicitCleanup::new(poll),
             data: ExplicitCleanup::new(data),
+            failed: false,
         }.for_each(move |(events, ready)| {
             if let Some(p) = weak.upgrade() {
                 events.into_iter().for_each(|e| {
@@ -54,13 +70,13 @@ impl IoEventStreams {
                       if let Some(ev) = io.0.get(&e.0) {
                           cb = ev.cb;
                           fd = ev.fd;
-                          ready_pulse = flags::mio_to_pulse(ready & e.1);
+                          ready_pulse = flags::mio_to_pulse(ready & (e.1 | flags::error_flags()));
                           userdata = ev.userdata;
                       } else {
                           return ();
                       }
                     }
-                    unsafe { cb.unwrap()(p.get_api(), e.0 as *mut pa_io_event, fd, ready_pulse, userdata) };
+                    p.dispatch(EventKind::Io, || unsafe { cb.unwrap()(p.get_api(), e.0 as *mut pa_io_event, fd, ready_pulse, userdata) });
                 });
                 Ok(())
             } else {
@@ -103,9 +119,10 @@ impl IoEventStreams {
 
 impl IoEventStream {
     fn do_poll(&mut self) -> Async<Option<<Self as Stream>::Item>> {
-        if !self.parent.upgrade().is_some() {
-            return Async::Ready(None);
-        }
+        let parent = match self.parent.upgrade() {
+            Some(p) => p,
+            None => return Async::Ready(None),
+        };
 
         {
             let mut data = self.data.borrow_mut();
@@ -123,17 +140,35 @@ impl IoEventStream {
             return Async::Ready(None);
         }
 
-        match self.poll.poll_ready(events)
-        {
-            Async::Ready(ready) => {
-                let v = self.data.borrow().events.iter()
-                        .filter(|&(_, m)| !(*m & ready).is_empty())
-                        .map(|(i, m)| (*i, *m))
-                        .collect(); 
-                Async::Ready(Some((v, ready)))
-            },
-            Async::NotReady => return Async::NotReady,
+        // Errors are reported to libpulse only once, it will usually close the
+        // connection in response.
+        if self.failed {
+            return Async::NotReady;
         }
+
+        let ready = match *self.poll {
+            Some(ref poll) => match poll.poll_ready(events) {
+                Ok(Async::Ready(ready)) => ready,
+                Ok(Async::NotReady) => return Async::NotReady,
+                Err(e) => {
+                    #[cfg(feature = "tracing")]
+                    ::tracing::warn!(fd = self.fd, error = %e, "unable to poll file descriptor");
+                    parent.io_errors.send(PaIoError::new(self.fd, e));
+                    self.failed = true;
+                    flags::error_flags()
+                },
+            },
+            None => {
+                self.failed = true;
+                flags::error_flags()
+            },
+        };
+
+        let v = self.data.borrow().events.iter()
+                .filter(|&(_, m)| !((*m | flags::error_flags()) & ready).is_empty())
+                .map(|(i, m)| (*i, *m))
+                .collect(); 
+        Async::Ready(Some((v, ready)))
     }
 }
 
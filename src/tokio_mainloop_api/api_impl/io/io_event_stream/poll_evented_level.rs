@@ -2,7 +2,7 @@ use futures::Async;
 use libc;
 use mio::{self, Evented, Ready};
 use mio::unix::EventedFd;
-use std::io::Result as IoResult;
+use std::io::{self, Result as IoResult};
 use std::os::unix::io::RawFd;
 use tokio_core::reactor::{Handle, PollEvented};
 
@@ -24,17 +24,15 @@ impl PollEventedFdLevel {
         })
     }
 
-    pub fn poll_ready(&self, mask: Ready) -> Async<Ready> {
+    pub fn poll_ready(&self, mask: Ready) -> IoResult<Async<Ready>> {
         match self.poll_evented.poll_ready(mask) {
             Async::Ready(mut ready) => {
                 // Simulate level semantics by calling poll() on the file descriptor
                 // and checking for actual readiness.
                 let mut pollfd = libc::pollfd { fd: self.fd, events: flags::mio_to_poll(ready), revents: 0 };
-                match unsafe { libc::poll(&mut pollfd, 1, 0) } {
-                    //-1 => ???,
+                match poll_once(&mut pollfd)? {
                     0 => ready = Ready::empty(),
-                    1 => ready &= flags::poll_to_mio(pollfd.revents),
-                    _ => panic!("Unexpected return value from poll()"),
+                    _ => ready = (ready | flags::error_flags()) & flags::poll_to_mio(pollfd.revents),
                 }
 
                 if !(mask & !Ready::writable()).is_empty() && (ready & !Ready::writable()).is_empty() {
@@ -44,12 +42,26 @@ impl PollEventedFdLevel {
                     self.poll_evented.need_write();
                 }
                 if ready.is_empty() {
-                    Async::NotReady
+                    Ok(Async::NotReady)
                 } else {
-                    Async::Ready(ready)
+                    Ok(Async::Ready(ready))
                 }
             },
-            Async::NotReady => Async::NotReady,
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
+fn poll_once(pollfd: &mut libc::pollfd) -> IoResult<libc::c_int> {
+    loop {
+        match unsafe { libc::poll(pollfd, 1, 0) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            },
+            n => return Ok(n),
         }
     }
 }
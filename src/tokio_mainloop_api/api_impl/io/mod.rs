@@ -37,6 +37,11 @@ impl Io {
         });
     }
 
+    #[cfg(feature = "stats")]
+    pub fn count(&self) -> usize {
+        self.0.len()
+    }
+
     pub fn spawn(&mut self,
                  fd: RawFd,
                  events: pa_io_event_flags_t,
@@ -7,10 +7,16 @@ mod timer;
 use libc;
 use libpulse_sys::*;
 use std::cell::RefCell;
-use std::ptr::{null, null_mut};
 use std::rc::Rc;
 use std::rc::Weak;
 use tokio_core::reactor::Handle;
+use api_vtable::{self, EventKind, MainLoopBackend};
+use channel::BroadcastSender;
+use mainloop_api::PaQuitRequestStream;
+use super::{PaIoError, PaIoErrorStream};
+#[cfg(feature = "stats")]
+use super::PaMainLoopStats;
+use super::stats::Stats;
 use self::timer::Timers;
 use self::deferred::Deferred;
 use self::io::Io;
@@ -22,6 +28,9 @@ pub struct TokioMainLoopApiImpl {
     timers: RefCell<Timers>,
     deferred: RefCell<Deferred>,
     io: RefCell<Io>,
+    quit: BroadcastSender<libc::c_int>,
+    io_errors: BroadcastSender<PaIoError>,
+    stats: Stats,
 }
 
 impl TokioMainLoopApiImpl {
@@ -29,6 +38,24 @@ impl TokioMainLoopApiImpl {
         &self.api as *const _ as *mut _
     }
 
+    pub fn quit_requests(&self) -> PaQuitRequestStream {
+        PaQuitRequestStream::new(self.quit.subscribe())
+    }
+
+    pub fn io_errors(&self) -> PaIoErrorStream {
+        PaIoErrorStream::new(self.io_errors.subscribe())
+    }
+
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> PaMainLoopStats {
+        self.stats.snapshot(self.io.borrow().count(), self.timers.borrow().count(), self.deferred.borrow().count())
+    }
+
+    /// Runs the libpulse callback `f` of an event of the given kind.
+    fn dispatch<T, F>(&self, kind: EventKind, f: F) -> T where F: FnOnce() -> T {
+        api_vtable::dispatch(kind, || self.stats.measure(kind, f))
+    }
+
     fn weak_ref(&self) -> Weak<TokioMainLoopApiImpl> {
         self.weak_self_ref.borrow().as_ref().unwrap().clone()
     }
@@ -42,29 +69,17 @@ impl Drop for TokioMainLoopApiImpl {
     }
 }
 
-pub fn new(handle: &Handle) -> Rc<TokioMainLoopApiImpl> {
+pub fn new(handle: &Handle, deferred_budget: Option<u32>) -> Rc<TokioMainLoopApiImpl> {
     let mut intern = Rc::new(TokioMainLoopApiImpl {
         handle: handle.clone(),
-        api: pa_mainloop_api {
-            userdata: null_mut(),
-            io_new: Some(io_new_cb),
-            io_enable: Some(io_enable_cb),
-            io_free: Some(io_free_cb),
-            io_set_destroy: Some(io_set_destroy_cb),
-            time_new: Some(time_new_cb),
-            time_restart: Some(time_restart_cb),
-            time_free: Some(time_free_cb),
-            time_set_destroy: Some(time_set_destroy_cb),
-            defer_new: Some(defer_new_cb),
-            defer_enable: Some(defer_enable_cb),
-            defer_free: Some(defer_free_cb),
-            defer_set_destroy: Some(defer_set_destroy_cb),
-            quit: Some(quit_cb),
-        },
+        api: api_vtable::vtable::<TokioMainLoopApiImpl>(),
         weak_self_ref: RefCell::new(None),
         timers: RefCell::new(Timers::new()),
-        deferred: RefCell::new(Deferred::new()),
+        deferred: RefCell::new(Deferred::new(deferred_budget)),
         io: RefCell::new(Io::new()),
+        quit: BroadcastSender::new(),
+        io_errors: BroadcastSender::new(),
+        stats: Stats::default(),
     });
 
     {
@@ -78,97 +93,56 @@ pub fn new(handle: &Handle) -> Rc<TokioMainLoopApiImpl> {
     intern
 }
 
-unsafe fn run_api_function<T, F>(a: *mut pa_mainloop_api, f: F) -> T where F: Fn(&TokioMainLoopApiImpl) -> T {
-    assert!(a != null_mut());
-    assert!((*a).userdata != null_mut());
-    f(&*((*a).userdata as *const TokioMainLoopApiImpl))
-}
-
-unsafe extern "C" fn io_new_cb(a: *mut pa_mainloop_api,
-                        fd: libc::c_int,
-                        events: pa_io_event_flags_t,
-                        cb: pa_io_event_cb_t,
-                        userdata: *mut libc::c_void) -> *mut pa_io_event {
-    assert!(cb.is_some());
-    assert!(fd >= 0);
-    run_api_function(a, |data| data.io.borrow_mut().spawn(fd, events, cb, userdata, data))
-}
-
-unsafe extern "C" fn io_enable_cb(e: *mut pa_io_event,
-                                  events: pa_io_event_flags_t) {
-    assert!(e != null_mut());
-    Io::enable(e, events)
-}
+impl MainLoopBackend for TokioMainLoopApiImpl {
+    fn io_new(&self, fd: libc::c_int, events: pa_io_event_flags_t, cb: pa_io_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_io_event {
+        self.io.borrow_mut().spawn(fd, events, cb, userdata, self)
+    }
 
-unsafe extern "C" fn io_free_cb(e: *mut pa_io_event) {
-    assert!(e != null_mut());
-    Io::free(e)
-}
+    unsafe fn io_enable(e: *mut pa_io_event, events: pa_io_event_flags_t) {
+        Io::enable(e, events)
+    }
 
-unsafe extern "C" fn io_set_destroy_cb(e: *mut pa_io_event,
-                                       cb: pa_io_event_destroy_cb_t) {
-    assert!(e != null_mut());
-    Io::set_destroy_cb(e, cb)
-}
+    unsafe fn io_free(e: *mut pa_io_event) {
+        Io::free(e)
+    }
 
-unsafe extern "C" fn time_new_cb(a: *mut pa_mainloop_api,
-                                 tv: *const libc::timeval,
-                                 cb: pa_time_event_cb_t,
-                                 userdata: *mut libc::c_void) -> *mut pa_time_event {
-    assert!(cb.is_some());
-    run_api_function(a, |data| data.timers.borrow_mut().spawn(ref_from_ptr(tv), cb, userdata, data))
-}
+    unsafe fn io_set_destroy(e: *mut pa_io_event, cb: pa_io_event_destroy_cb_t) {
+        Io::set_destroy_cb(e, cb)
+    }
 
-unsafe extern "C" fn time_restart_cb(e: *mut pa_time_event,
-                                     tv: *const libc::timeval) {
-    assert!(e != null_mut());
-    Timers::restart(e, ref_from_ptr(tv));
-}
+    fn time_new(&self, tv: Option<&libc::timeval>, cb: pa_time_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_time_event {
+        self.timers.borrow_mut().spawn(tv, cb, userdata, self)
+    }
 
-unsafe extern "C" fn time_free_cb(e: *mut pa_time_event) {
-    assert!(e != null_mut());
-    Timers::free(e)
-}
+    unsafe fn time_restart(e: *mut pa_time_event, tv: Option<&libc::timeval>) {
+        Timers::restart(e, tv)
+    }
 
-unsafe extern "C" fn time_set_destroy_cb(e: *mut pa_time_event,
-                                         cb: pa_time_event_destroy_cb_t) {
-    assert!(e != null_mut());
-    Timers::set_destroy_cb(e, cb)
-}
+    unsafe fn time_free(e: *mut pa_time_event) {
+        Timers::free(e)
+    }
 
-unsafe extern "C" fn defer_new_cb(a: *mut pa_mainloop_api,
-                                  cb: pa_defer_event_cb_t,
-                                  userdata: *mut libc::c_void) -> *mut pa_defer_event {
-    assert!(cb.is_some());
-    run_api_function(a, |data| Deferred::add(data, cb, userdata))
-}
+    unsafe fn time_set_destroy(e: *mut pa_time_event, cb: pa_time_event_destroy_cb_t) {
+        Timers::set_destroy_cb(e, cb)
+    }
 
-unsafe extern "C" fn defer_enable_cb(e: *mut pa_defer_event,
-                                     b: libc::c_int) {
-    assert!(e != null_mut());
-    Deferred::enable(e, b != 0)
-}
+    fn defer_new(&self, cb: pa_defer_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_defer_event {
+        Deferred::add(self, cb, userdata)
+    }
 
-unsafe extern "C" fn defer_free_cb(e: *mut pa_defer_event) {
-    assert!(e != null_mut());
-    Deferred::free(e)
-}
+    unsafe fn defer_enable(e: *mut pa_defer_event, enabled: bool) {
+        Deferred::enable(e, enabled)
+    }
 
-unsafe extern "C" fn defer_set_destroy_cb(e: *mut pa_defer_event,
-                                          cb: pa_defer_event_destroy_cb_t) {
-    assert!(e != null_mut());
-    Deferred::set_destroy(e, cb)
-}
+    unsafe fn defer_free(e: *mut pa_defer_event) {
+        Deferred::free(e)
+    }
 
-unsafe extern "C" fn quit_cb(_a: *mut pa_mainloop_api,
-                             _retval: libc::c_int) {
-    eprintln!("The quit callback is not implemented!");
-}
+    unsafe fn defer_set_destroy(e: *mut pa_defer_event, cb: pa_defer_event_destroy_cb_t) {
+        Deferred::set_destroy(e, cb)
+    }
 
-unsafe fn ref_from_ptr<'a, T>(ptr: *const T) -> Option<&'a T> {
-    if ptr == null() {
-        None
-    } else {
-        Some(&*ptr)
+    fn quit(&self, retval: libc::c_int) {
+        self.quit.send(retval)
     }
 }
@@ -3,11 +3,12 @@ use libpulse_sys::*;
 use std::collections::HashMap;
 use std::mem;
 use std::ptr::null_mut;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio_core::reactor::Timeout;
 use futures::prelude::*;
 use futures::{future, task};
 use futures::unsync::oneshot;
+use api_vtable::EventKind;
+use timeval;
 
 use super::TokioMainLoopApiImpl;
 use super::completion_future::CompletionFuture;
@@ -28,17 +29,17 @@ impl Timers {
         Timers(HashMap::new())
     }
 
+    #[cfg(feature = "stats")]
+    pub fn count(&self) -> usize {
+        self.0.len()
+    }
+
     fn spawn_timeout_handler(data: &TokioMainLoopApiImpl,
                              reference: *mut pa_time_event,
                              tv: libc::timeval) -> oneshot::Sender<CompletionFuture> {
-        assert!(tv.tv_usec < 1000000);
-        let timeout = match (UNIX_EPOCH + Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000)).duration_since(SystemTime::now()) {
-            Ok(t) => t,
-            Err(_) => Duration::new(0, 0),
-        };
         let weak = data.weak_ref();
         let (send, rec) = oneshot::channel();
-        let f = Timeout::new(timeout, &data.handle).expect("Unable to create timeout")
+        let f = Timeout::new_at(timeval::deadline(&tv), &data.handle).expect("Unable to create timeout")
                 .select2(rec)
                 .then(move |r| {
                     match r {
@@ -61,7 +62,7 @@ impl Timers {
 
                                     if let Some(cb) = cb {
                                         if reference != null_mut() {
-                                            unsafe { cb(p.get_api(), reference, &tv, userdata) };
+                                            p.dispatch(EventKind::Time, || unsafe { cb(p.get_api(), reference, &tv, userdata) });
                                             return CompletionFuture::Ok
                                         }
                                     }
@@ -0,0 +1,82 @@
+use futures::{Async, Poll, Stream};
+use futures_core::Stream as StdStream;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::os::unix::io::RawFd;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::{Context, Poll as StdPoll};
+
+use channel::{BroadcastReceiver, TaskWaker};
+use panic_guard;
+
+/// An error that occurred while watching a file descriptor for pulseaudio.
+///
+/// libpulse is notified of such errors with a hangup and error event on the file
+/// descriptor, which usually makes it close the connection.
+#[derive(Debug, Clone)]
+pub struct PaIoError {
+    fd: RawFd,
+    error: Rc<io::Error>,
+}
+
+impl PaIoError {
+    pub(crate) fn new(fd: RawFd, error: io::Error) -> PaIoError {
+        PaIoError { fd, error: Rc::new(error) }
+    }
+
+    /// Returns the file descriptor the error occurred on.
+    pub fn fd(&self) -> RawFd {
+        self.fd
+    }
+
+    /// Returns the underlying I/O error.
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+}
+
+impl fmt::Display for PaIoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Unable to watch file descriptor {}: {}", self.fd, self.error)
+    }
+}
+
+impl Error for PaIoError {}
+
+/// A stream of errors that occurred while watching file descriptors.
+///
+/// This type implements both `futures_core::Stream` and `futures` 0.1 `Stream`.
+pub struct PaIoErrorStream(BroadcastReceiver<PaIoError>);
+
+impl PaIoErrorStream {
+    pub(crate) fn new(receiver: BroadcastReceiver<PaIoError>) -> PaIoErrorStream {
+        PaIoErrorStream(receiver)
+    }
+
+    fn poll_impl(&mut self, waker: &TaskWaker) -> StdPoll<Option<PaIoError>> {
+        panic_guard::resume();
+        self.0.poll_next(waker)
+    }
+}
+
+impl Stream for PaIoErrorStream {
+    type Item = PaIoError;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Option<PaIoError>, ()> {
+        match self.poll_impl(&TaskWaker::current()) {
+            StdPoll::Ready(e) => Ok(Async::Ready(e)),
+            StdPoll::Pending => Ok(Async::NotReady),
+        }
+    }
+}
+
+impl StdStream for PaIoErrorStream {
+    type Item = PaIoError;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> StdPoll<Option<PaIoError>> {
+        self.get_mut().poll_impl(&TaskWaker::from_context(cx))
+    }
+}
@@ -2,23 +2,34 @@
 //!
 //! [`PaMainLoopApi`]: ../mainloop_api/trait.PaMainLoopApi.html
 mod api_impl;
+mod io_error;
+mod stats;
 
 use libpulse_sys::*;
 use std::rc::Rc;
 use tokio_core::reactor::Handle;
 
 use self::api_impl::TokioMainLoopApiImpl;
-use super::mainloop_api::PaMainLoopApi;
+use super::mainloop_api::{PaMainLoopApi, PaQuitRequestStream};
+
+pub use self::io_error::{PaIoError, PaIoErrorStream};
+#[cfg(feature = "stats")]
+pub use self::stats::PaMainLoopStats;
 
 /// Implementation of [`PaMainLoopApi`] for `tokio_core`.
 ///
 /// This implementation can be used with any mainloop that uses
 /// `tokio_core::rector::Core`.
 ///
-/// Note that while pulseaudio deferred events are being handled,
+/// Note that by default, while pulseaudio deferred events are being handled,
 /// this implementation blocks all other events until all deferred
 /// events are disabled. This behaviour closely resembles the behaviour
-/// of pulseaudio's own mainloop.
+/// of pulseaudio's own mainloop. Use [`with_deferred_budget`](#method.with_deferred_budget)
+/// to limit the time spent on deferred events.
+///
+/// Panics in callbacks do not unwind through the internal tasks, which keep
+/// dispatching events. They are re-raised when a future or stream of this crate,
+/// e.g. [`quit_requests`](#method.quit_requests), is polled next on the same thread.
 ///
 /// [`PaMainLoopApi`]: ../mainloop_api/trait.PaMainLoopApi.html
 #[derive(Clone)]
@@ -31,7 +42,46 @@ impl PaMainLoopApiTokio {
     ///
     /// Requires a handle to a `tokio_core::rector::Core`.
     pub fn new(handle: &Handle) -> PaMainLoopApiTokio {
-        PaMainLoopApiTokio { intern: api_impl::new(handle) }
+        PaMainLoopApiTokio { intern: api_impl::new(handle, None) }
+    }
+
+    /// Creates a new pulseaudio mainloop API that dispatches deferred events in bounded batches.
+    ///
+    /// Each time the reactor polls the deferred events, at most `rounds` rounds are
+    /// dispatched, where each round calls every enabled deferred event once. Afterwards,
+    /// control is yielded back to the reactor, so that enabled deferred events cannot
+    /// starve other events.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rounds` is zero.
+    pub fn with_deferred_budget(handle: &Handle, rounds: u32) -> PaMainLoopApiTokio {
+        assert!(rounds > 0, "The deferred event budget must not be zero");
+        PaMainLoopApiTokio { intern: api_impl::new(handle, Some(rounds)) }
+    }
+
+    /// Returns a stream of requests to quit the mainloop.
+    ///
+    /// Applications should shut down when a request arrives. See
+    /// [`PaQuitRequestStream`](../mainloop_api/struct.PaQuitRequestStream.html).
+    pub fn quit_requests(&self) -> PaQuitRequestStream {
+        self.intern.quit_requests()
+    }
+
+    /// Returns a stream of errors that occurred while watching file descriptors.
+    ///
+    /// Such errors are reported to libpulse as hangup and error events, this stream
+    /// only exists for diagnostic purposes.
+    pub fn io_errors(&self) -> PaIoErrorStream {
+        self.intern.io_errors()
+    }
+
+    /// Returns a snapshot of the statistics of this mainloop API.
+    ///
+    /// Only available with the `stats` feature.
+    #[cfg(feature = "stats")]
+    pub fn stats(&self) -> PaMainLoopStats {
+        self.intern.stats()
     }
 }
 
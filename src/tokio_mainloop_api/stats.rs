@@ -0,0 +1,103 @@
+//! Instrumentation of the mainloop, enabled with the `stats` feature.
+#[cfg(feature = "stats")]
+use std::cell::Cell;
+#[cfg(feature = "stats")]
+use std::time::{Duration, Instant};
+
+use api_vtable::EventKind;
+
+/// A snapshot of the statistics of a [`PaMainLoopApiTokio`](struct.PaMainLoopApiTokio.html).
+///
+/// Counters and times are accumulated since the mainloop API was created.
+#[cfg(feature = "stats")]
+#[derive(Debug, Clone, Default)]
+pub struct PaMainLoopStats {
+    /// Number of live io events.
+    pub io_events: usize,
+    /// Number of live time events.
+    pub time_events: usize,
+    /// Number of live deferred events.
+    pub defer_events: usize,
+    /// Number of dispatched io event callbacks.
+    pub io_dispatched: u64,
+    /// Number of dispatched time event callbacks.
+    pub time_dispatched: u64,
+    /// Number of dispatched deferred event callbacks.
+    pub defer_dispatched: u64,
+    /// Total time spent in libpulse callbacks.
+    pub callback_time: Duration,
+    /// Longest time spent in a single libpulse callback.
+    pub max_callback_time: Duration,
+    /// Number of times the deferred events were polled.
+    pub deferred_polls: u64,
+    /// Total number of deferred dispatch rounds.
+    pub deferred_rounds: u64,
+    /// Largest number of deferred dispatch rounds in a single poll.
+    pub max_deferred_rounds: u32,
+}
+
+#[cfg(feature = "stats")]
+#[derive(Default)]
+pub struct Stats {
+    dispatched: [Cell<u64>; 3],
+    callback_time: Cell<Duration>,
+    max_callback_time: Cell<Duration>,
+    deferred_polls: Cell<u64>,
+    deferred_rounds: Cell<u64>,
+    max_deferred_rounds: Cell<u32>,
+}
+
+#[cfg(not(feature = "stats"))]
+#[derive(Default)]
+pub struct Stats;
+
+#[cfg(feature = "stats")]
+impl Stats {
+    /// Runs the callback `f` of an event of the given kind.
+    pub fn measure<T, F>(&self, kind: EventKind, f: F) -> T where F: FnOnce() -> T {
+        let start = Instant::now();
+        let ret = f();
+        let elapsed = start.elapsed();
+        let dispatched = &self.dispatched[kind as usize];
+        dispatched.set(dispatched.get() + 1);
+        self.callback_time.set(self.callback_time.get() + elapsed);
+        if elapsed > self.max_callback_time.get() {
+            self.max_callback_time.set(elapsed);
+        }
+        ret
+    }
+
+    /// Records a poll of the deferred events.
+    pub fn deferred_poll(&self, rounds: u32) {
+        self.deferred_polls.set(self.deferred_polls.get() + 1);
+        self.deferred_rounds.set(self.deferred_rounds.get() + rounds as u64);
+        if rounds > self.max_deferred_rounds.get() {
+            self.max_deferred_rounds.set(rounds);
+        }
+    }
+
+    pub fn snapshot(&self, io_events: usize, time_events: usize, defer_events: usize) -> PaMainLoopStats {
+        PaMainLoopStats {
+            io_events,
+            time_events,
+            defer_events,
+            io_dispatched: self.dispatched[EventKind::Io as usize].get(),
+            time_dispatched: self.dispatched[EventKind::Time as usize].get(),
+            defer_dispatched: self.dispatched[EventKind::Defer as usize].get(),
+            callback_time: self.callback_time.get(),
+            max_callback_time: self.max_callback_time.get(),
+            deferred_polls: self.deferred_polls.get(),
+            deferred_rounds: self.deferred_rounds.get(),
+            max_deferred_rounds: self.max_deferred_rounds.get(),
+        }
+    }
+}
+
+#[cfg(not(feature = "stats"))]
+impl Stats {
+    pub fn measure<T, F>(&self, _kind: EventKind, f: F) -> T where F: FnOnce() -> T {
+        f()
+    }
+
+    pub fn deferred_poll(&self, _rounds: u32) {}
+}
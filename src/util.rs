@@ -0,0 +1,25 @@
+//! Conversions of values returned by libpulse.
+use libc;
+use libpulse_sys::PA_INVALID_INDEX;
+use std::ffi::CStr;
+use std::ptr::null;
+
+/// Copies a C string, returning `None` if `ptr` is null.
+///
+/// `ptr` must be null or point to a valid C string.
+pub unsafe fn string_from_ptr(ptr: *const libc::c_char) -> Option<String> {
+    if ptr == null() {
+        None
+    } else {
+        Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+}
+
+/// Maps `PA_INVALID_INDEX` to `None`.
+pub fn index_to_option(idx: u32) -> Option<u32> {
+    if idx == PA_INVALID_INDEX {
+        None
+    } else {
+        Some(idx)
+    }
+}
@@ -0,0 +1,399 @@
+//! A mainloop with a virtual clock for deterministic tests.
+use libc;
+use libpulse_sys::*;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::mem;
+use std::os::unix::io::RawFd;
+use std::rc::Rc;
+use std::time::Duration;
+
+use api_vtable::{self, EventKind, MainLoopBackend};
+use channel::BroadcastSender;
+use mainloop_api::{PaMainLoopApi, PaQuitRequestStream};
+use panic_guard;
+use timeval;
+
+struct IoEvent {
+    fd: RawFd,
+    events: Cell<pa_io_event_flags_t>,
+    dead: Cell<bool>,
+    cb: pa_io_event_cb_t,
+    destroy_cb: Cell<pa_io_event_destroy_cb_t>,
+    userdata: *mut libc::c_void,
+}
+
+struct TimeEvent {
+    parent: *const VirtualMainLoopImpl,
+    // The virtual time at which the event fires, and the timeval it was created with.
+    deadline: Cell<Option<(Duration, libc::timeval)>>,
+    dead: Cell<bool>,
+    cb: pa_time_event_cb_t,
+    destroy_cb: Cell<pa_time_event_destroy_cb_t>,
+    userdata: *mut libc::c_void,
+}
+
+struct DeferEvent {
+    enabled: Cell<bool>,
+    dead: Cell<bool>,
+    cb: pa_defer_event_cb_t,
+    destroy_cb: Cell<pa_defer_event_destroy_cb_t>,
+    userdata: *mut libc::c_void,
+}
+
+struct VirtualMainLoopImpl {
+    api: pa_mainloop_api,
+    now: Cell<Duration>,
+    io: RefCell<Vec<Rc<IoEvent>>>,
+    time: RefCell<Vec<Rc<TimeEvent>>>,
+    defer: RefCell<Vec<Rc<DeferEvent>>>,
+    ready: RefCell<HashMap<RawFd, pa_io_event_flags_t>>,
+    quit: BroadcastSender<libc::c_int>,
+}
+
+impl VirtualMainLoopImpl {
+    fn get_api(&self) -> *mut pa_mainloop_api {
+        &self.api as *const _ as *mut _
+    }
+
+    // Converts a timeval from libpulse to virtual time. The delay is rounded to
+    // microseconds, the resolution of a timeval.
+    fn virtual_deadline(&self, tv: &libc::timeval) -> Duration {
+        let remaining = timeval::remaining(tv);
+        let us = (remaining.subsec_nanos() + 500) / 1000;
+        // Duration::new carries nanoseconds over into seconds.
+        self.now.get() + Duration::new(remaining.as_secs(), us * 1000)
+    }
+
+    fn dispatch_defer(&self) -> u32 {
+        let events = self.defer.borrow().clone();
+        let mut n = 0;
+        for ev in events.iter().filter(|ev| ev.enabled.get() && !ev.dead.get()) {
+            api_vtable::dispatch(EventKind::Defer, || unsafe { ev.cb.unwrap()(self.get_api(), ev_ptr(ev), ev.userdata) });
+            n += 1;
+        }
+        n
+    }
+
+    fn dispatch_time(&self) -> u32 {
+        let now = self.now.get();
+        let mut events: Vec<_> = self.time.borrow().iter()
+            .filter(|ev| !ev.dead.get() && ev.deadline.get().map_or(false, |(d, _)| d <= now))
+            .cloned()
+            .collect();
+        // The sort is stable, events with equal deadlines fire in order of creation.
+        events.sort_by_key(|ev| ev.deadline.get().unwrap().0);
+        let mut n = 0;
+        for ev in events.iter() {
+            // An earlier callback may have freed, restarted or disabled this event.
+            let tv = match ev.deadline.get() {
+                Some((d, tv)) if d <= now && !ev.dead.get() => tv,
+                _ => continue,
+            };
+            // Time events fire only once, the callback may restart them.
+            ev.deadline.set(None);
+            api_vtable::dispatch(EventKind::Time, || unsafe { ev.cb.unwrap()(self.get_api(), ev_ptr(ev), &tv, ev.userdata) });
+            n += 1;
+        }
+        n
+    }
+
+    fn dispatch_io(&self) -> u32 {
+        let events = self.io.borrow().clone();
+        let mut n = 0;
+        for ev in events.iter().filter(|ev| !ev.dead.get()) {
+            let ready = self.ready.borrow().get(&ev.fd).cloned().unwrap_or(PA_IO_EVENT_NULL);
+            // Like poll(), hangups and errors are reported even if they were not requested.
+            let ready = ready & (ev.events.get() | PA_IO_EVENT_HANGUP | PA_IO_EVENT_ERROR);
+            if ready != PA_IO_EVENT_NULL {
+                api_vtable::dispatch(EventKind::Io, || unsafe { ev.cb.unwrap()(self.get_api(), ev_ptr(ev), ev.fd, ready, ev.userdata) });
+                n += 1;
+            }
+        }
+        n
+    }
+
+    fn cleanup(&self) {
+        let api = self.get_api();
+        for ev in remove_dead(&self.io, |ev| ev.dead.get()) {
+            if let Some(cb) = ev.destroy_cb.get() {
+                unsafe { cb(api, ev_ptr(&ev), ev.userdata) };
+            }
+        }
+        for ev in remove_dead(&self.time, |ev| ev.dead.get()) {
+            if let Some(cb) = ev.destroy_cb.get() {
+                unsafe { cb(api, ev_ptr(&ev), ev.userdata) };
+            }
+        }
+        for ev in remove_dead(&self.defer, |ev| ev.dead.get()) {
+            if let Some(cb) = ev.destroy_cb.get() {
+                unsafe { cb(api, ev_ptr(&ev), ev.userdata) };
+            }
+        }
+    }
+}
+
+impl Drop for VirtualMainLoopImpl {
+    fn drop(&mut self) {
+        let api = self.get_api();
+        mem::replace(self.io.get_mut(), Vec::new()).into_iter().for_each(|ev| {
+            if let Some(cb) = ev.destroy_cb.get() {
+                unsafe { cb(api, ev_ptr(&ev), ev.userdata) };
+            }
+        });
+        mem::replace(self.time.get_mut(), Vec::new()).into_iter().for_each(|ev| {
+            if let Some(cb) = ev.destroy_cb.get() {
+                unsafe { cb(api, ev_ptr(&ev), ev.userdata) };
+            }
+        });
+        mem::replace(self.defer.get_mut(), Vec::new()).into_iter().for_each(|ev| {
+            if let Some(cb) = ev.destroy_cb.get() {
+                unsafe { cb(api, ev_ptr(&ev), ev.userdata) };
+            }
+        });
+    }
+}
+
+/// Implementation of [`PaMainLoopApi`] with a virtual clock, for deterministic tests.
+///
+/// Nothing happens until the mainloop is driven explicitly:
+///
+/// * [`iterate`](#method.iterate) runs a single iteration, dispatching deferred events,
+///   due time events and ready io events.
+/// * [`advance`](#method.advance) moves the virtual clock forward and fires the time
+///   events that become due, without any real sleeps.
+/// * [`set_io_ready`](#method.set_io_ready) injects readiness of file descriptors. The
+///   file descriptors are never polled.
+///
+/// Time events are scheduled relative to the virtual clock when they are created or
+/// restarted. Their delay is rounded to microseconds.
+///
+/// [`PaMainLoopApi`]: ../mainloop_api/trait.PaMainLoopApi.html
+#[derive(Clone)]
+pub struct VirtualMainLoop {
+    intern: Rc<VirtualMainLoopImpl>,
+}
+
+impl VirtualMainLoop {
+    /// Creates a new mainloop with the virtual clock at zero.
+    pub fn new() -> VirtualMainLoop {
+        let mut intern = Rc::new(VirtualMainLoopImpl {
+            api: api_vtable::vtable::<VirtualMainLoopImpl>(),
+            now: Cell::new(Duration::new(0, 0)),
+            io: RefCell::new(Vec::new()),
+            time: RefCell::new(Vec::new()),
+            defer: RefCell::new(Vec::new()),
+            ready: RefCell::new(HashMap::new()),
+            quit: BroadcastSender::new(),
+        });
+        {
+            let intern_mut = Rc::get_mut(&mut intern).unwrap();
+            let ptr = intern_mut as *mut VirtualMainLoopImpl as *mut libc::c_void;
+            intern_mut.api.userdata = ptr;
+        }
+        VirtualMainLoop { intern }
+    }
+
+    /// Returns the current time of the virtual clock.
+    pub fn now(&self) -> Duration {
+        self.intern.now.get()
+    }
+
+    /// Returns the virtual time at which the next enabled time event fires.
+    pub fn next_deadline(&self) -> Option<Duration> {
+        self.intern.time.borrow().iter()
+            .filter(|ev| !ev.dead.get())
+            .filter_map(|ev| ev.deadline.get().map(|(d, _)| d))
+            .min()
+    }
+
+    /// Advances the virtual clock by `duration`.
+    ///
+    /// The time events that become due are fired in order of their deadlines, with the
+    /// clock set to the respective deadline. This includes events that are restarted by
+    /// these callbacks. Deferred and io events are not dispatched.
+    ///
+    /// Returns the number of dispatched events.
+    pub fn advance(&self, duration: Duration) -> u32 {
+        let target = self.now() + duration;
+        let mut n = 0;
+        while let Some(deadline) = self.next_deadline().filter(|&d| d <= target) {
+            if deadline > self.now() {
+                self.intern.now.set(deadline);
+            }
+            n += self.intern.dispatch_time();
+            self.finish_dispatch();
+        }
+        self.intern.now.set(target);
+        n
+    }
+
+    /// Runs a single iteration of the mainloop.
+    ///
+    /// Like pulseaudio's own mainloop, an iteration dispatches only the deferred events
+    /// while any of them is enabled. Otherwise, it dispatches the time events that are
+    /// due and the io events whose file descriptors are ready.
+    ///
+    /// Returns the number of dispatched events.
+    pub fn iterate(&self) -> u32 {
+        let mut n = self.intern.dispatch_defer();
+        if n == 0 {
+            n += self.intern.dispatch_time();
+            n += self.intern.dispatch_io();
+        }
+        self.finish_dispatch();
+        n
+    }
+
+    /// Runs iterations until no more events are dispatched, at most `max_iterations` times.
+    ///
+    /// Returns the number of dispatched events.
+    pub fn run_until_idle(&self, max_iterations: u32) -> u32 {
+        let mut n = 0;
+        for _ in 0..max_iterations {
+            match self.iterate() {
+                0 => break,
+                i => n += i,
+            }
+        }
+        n
+    }
+
+    /// Returns the file descriptors watched by io events, with the requested events.
+    ///
+    /// Disabled io events are included with `PA_IO_EVENT_NULL`.
+    pub fn watched_fds(&self) -> Vec<(RawFd, pa_io_event_flags_t)> {
+        self.intern.io.borrow().iter()
+            .filter(|ev| !ev.dead.get())
+            .map(|ev| (ev.fd, ev.events.get()))
+            .collect()
+    }
+
+    /// Sets the readiness of a file descriptor.
+    ///
+    /// The readiness is level-triggered: the io events on `fd` are dispatched in every
+    /// iteration until the readiness is reset with `PA_IO_EVENT_NULL`.
+    pub fn set_io_ready(&self, fd: RawFd, events: pa_io_event_flags_t) {
+        let mut ready = self.intern.ready.borrow_mut();
+        if events == PA_IO_EVENT_NULL {
+            ready.remove(&fd);
+        } else {
+            ready.insert(fd, events);
+        }
+    }
+
+    /// Returns a stream of requests to quit the mainloop.
+    pub fn quit_requests(&self) -> PaQuitRequestStream {
+        PaQuitRequestStream::new(self.intern.quit.subscribe())
+    }
+
+    fn finish_dispatch(&self) {
+        self.intern.cleanup();
+        panic_guard::resume();
+    }
+}
+
+unsafe impl PaMainLoopApi for VirtualMainLoop {
+    fn get_api(&self) -> *mut pa_mainloop_api {
+        self.intern.get_api()
+    }
+}
+
+fn ev_ptr<T, E>(ev: &Rc<T>) -> *mut E {
+    &**ev as *const T as *mut E
+}
+
+fn remove_dead<T, F>(events: &RefCell<Vec<Rc<T>>>, is_dead: F) -> Vec<Rc<T>> where F: Fn(&T) -> bool {
+    let mut events = events.borrow_mut();
+    let (dead, alive) = mem::replace(&mut *events, Vec::new()).into_iter().partition(|ev| is_dead(ev));
+    *events = alive;
+    dead
+}
+
+impl MainLoopBackend for VirtualMainLoopImpl {
+    fn io_new(&self, fd: libc::c_int, events: pa_io_event_flags_t, cb: pa_io_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_io_event {
+        let ev = Rc::new(IoEvent {
+            fd,
+            events: Cell::new(events),
+            dead: Cell::new(false),
+            cb,
+            destroy_cb: Cell::new(None),
+            userdata,
+        });
+        let ptr = ev_ptr(&ev);
+        self.io.borrow_mut().push(ev);
+        ptr
+    }
+
+    unsafe fn io_enable(e: *mut pa_io_event, events: pa_io_event_flags_t) {
+        (*(e as *const IoEvent)).events.set(events);
+    }
+
+    unsafe fn io_free(e: *mut pa_io_event) {
+        (*(e as *const IoEvent)).dead.set(true);
+    }
+
+    unsafe fn io_set_destroy(e: *mut pa_io_event, cb: pa_io_event_destroy_cb_t) {
+        (*(e as *const IoEvent)).destroy_cb.set(cb);
+    }
+
+    fn time_new(&self, tv: Option<&libc::timeval>, cb: pa_time_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_time_event {
+        let ev = Rc::new(TimeEvent {
+            parent: self,
+            deadline: Cell::new(tv.map(|tv| (self.virtual_deadline(tv), *tv))),
+            dead: Cell::new(false),
+            cb,
+            destroy_cb: Cell::new(None),
+            userdata,
+        });
+        let ptr = ev_ptr(&ev);
+        self.time.borrow_mut().push(ev);
+        ptr
+    }
+
+    unsafe fn time_restart(e: *mut pa_time_event, tv: Option<&libc::timeval>) {
+        let ev = &*(e as *const TimeEvent);
+        ev.deadline.set(tv.map(|tv| ((*ev.parent).virtual_deadline(tv), *tv)));
+    }
+
+    unsafe fn time_free(e: *mut pa_time_event) {
+        let ev = &*(e as *const TimeEvent);
+        ev.deadline.set(None);
+        ev.dead.set(true);
+    }
+
+    unsafe fn time_set_destroy(e: *mut pa_time_event, cb: pa_time_event_destroy_cb_t) {
+        (*(e as *const TimeEvent)).destroy_cb.set(cb);
+    }
+
+    fn defer_new(&self, cb: pa_defer_event_cb_t, userdata: *mut libc::c_void) -> *mut pa_defer_event {
+        let ev = Rc::new(DeferEvent {
+            enabled: Cell::new(true),
+            dead: Cell::new(false),
+            cb,
+            destroy_cb: Cell::new(None),
+            userdata,
+        });
+        let ptr = ev_ptr(&ev);
+        self.defer.borrow_mut().push(ev);
+        ptr
+    }
+
+    unsafe fn defer_enable(e: *mut pa_defer_event, enabled: bool) {
+        (*(e as *const DeferEvent)).enabled.set(enabled);
+    }
+
+    unsafe fn defer_free(e: *mut pa_defer_event) {
+        let ev = &*(e as *const DeferEvent);
+        ev.enabled.set(false);
+        ev.dead.set(true);
+    }
+
+    unsafe fn defer_set_destroy(e: *mut pa_defer_event, cb: pa_defer_event_destroy_cb_t) {
+        (*(e as *const DeferEvent)).destroy_cb.set(cb);
+    }
+
+    fn quit(&self, retval: libc::c_int) {
+        self.quit.send(retval)
+    }
+}
@@ -0,0 +1,374 @@
+//! Conformance tests for `PaMainLoopApi` implementations.
+//!
+//! The tests only use the raw `pa_mainloop_api` vtable, so they apply to every backend.
+//! A backend is plugged in by implementing [`Driver`], and every test function takes
+//! a fresh driver.
+#![allow(dead_code)]
+
+use libc;
+use libpulse_sys::*;
+use pulseaudio::mainloop_api::PaMainLoopApi;
+use std::cell::{Cell, RefCell};
+use std::os::unix::io::RawFd;
+use std::ptr::null_mut;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Drives a mainloop for the conformance tests.
+pub trait Driver {
+    type Api: PaMainLoopApi;
+
+    /// Returns the mainloop API under test.
+    fn api(&self) -> Self::Api;
+
+    /// Runs the mainloop until `done` returns true. Returns false if that did not
+    /// happen within a few seconds.
+    fn run_until(&mut self, done: &Fn() -> bool) -> bool;
+
+    /// Runs the mainloop for the given time.
+    fn run_for(&mut self, duration: Duration);
+
+    /// Tells the driver whether `fd` is readable. Only needed for backends that do not
+    /// poll file descriptors themselves.
+    fn set_readable(&mut self, _fd: RawFd, _readable: bool) {}
+}
+
+/// Events recorded by the callbacks of a test, together with the API they may call.
+struct Recorder {
+    api: *mut pa_mainloop_api,
+    log: RefCell<Vec<&'static str>>,
+    count: Cell<u32>,
+    io: Cell<*mut pa_io_event>,
+    time: Cell<*mut pa_time_event>,
+    defer: Cell<*mut pa_defer_event>,
+    other_defer: Cell<*mut pa_defer_event>,
+}
+
+impl Recorder {
+    fn new<A: PaMainLoopApi>(api: &A) -> Box<Recorder> {
+        Box::new(Recorder {
+            api: api.get_api(),
+            log: RefCell::new(Vec::new()),
+            count: Cell::new(0),
+            io: Cell::new(null_mut()),
+            time: Cell::new(null_mut()),
+            defer: Cell::new(null_mut()),
+            other_defer: Cell::new(null_mut()),
+        })
+    }
+
+    fn userdata(&self) -> *mut libc::c_void {
+        self as *const Recorder as *mut libc::c_void
+    }
+
+    fn push(&self, entry: &'static str) {
+        self.log.borrow_mut().push(entry);
+    }
+
+    fn log(&self) -> Vec<&'static str> {
+        self.log.borrow().clone()
+    }
+
+    fn occurrences(&self, entry: &str) -> usize {
+        self.log.borrow().iter().filter(|e| **e == entry).count()
+    }
+}
+
+unsafe fn recorder<'a>(userdata: *mut libc::c_void) -> &'a Recorder {
+    assert!(userdata != null_mut());
+    &*(userdata as *const Recorder)
+}
+
+fn timeval_after(delay: Duration) -> libc::timeval {
+    let t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + delay;
+    libc::timeval { tv_sec: t.as_secs() as libc::time_t, tv_usec: t.subsec_micros() as libc::suseconds_t }
+}
+
+struct Pipe(RawFd, RawFd);
+
+impl Pipe {
+    fn new() -> Pipe {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        Pipe(fds[0], fds[1])
+    }
+
+    fn write_byte(&self) {
+        assert_eq!(unsafe { libc::write(self.1, b"x".as_ptr() as *const libc::c_void, 1) }, 1);
+    }
+
+    fn read_byte(&self) {
+        let mut buf = [0u8; 1];
+        assert_eq!(unsafe { libc::read(self.0, buf.as_mut_ptr() as *mut libc::c_void, 1) }, 1);
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.0);
+            libc::close(self.1);
+        }
+    }
+}
+
+extern "C" fn io_destroy_cb(_a: *mut pa_mainloop_api, _e: *mut pa_io_event, userdata: *mut libc::c_void) {
+    unsafe { recorder(userdata) }.push("io destroy");
+}
+
+extern "C" fn time_destroy_cb(_a: *mut pa_mainloop_api, _e: *mut pa_time_event, userdata: *mut libc::c_void) {
+    unsafe { recorder(userdata) }.push("time destroy");
+}
+
+extern "C" fn defer_destroy_cb(_a: *mut pa_mainloop_api, _e: *mut pa_defer_event, userdata: *mut libc::c_void) {
+    unsafe { recorder(userdata) }.push("defer destroy");
+}
+
+/// An io event is dispatched while its file descriptor is readable and enabled, and
+/// not after it was disabled.
+pub fn io_enable_disable<D: Driver>(mut driver: D) {
+    extern "C" fn cb(a: *mut pa_mainloop_api, e: *mut pa_io_event, _fd: libc::c_int, events: pa_io_event_flags_t, userdata: *mut libc::c_void) {
+        let r = unsafe { recorder(userdata) };
+        assert!(events & PA_IO_EVENT_INPUT != 0);
+        r.count.set(r.count.get() + 1);
+        // Disable the event without consuming the data.
+        unsafe { (*a).io_enable.unwrap()(e, PA_IO_EVENT_NULL) };
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let pipe = Pipe::new();
+    let a = api.get_api();
+    let e = unsafe { (*a).io_new.unwrap()(a, pipe.0, PA_IO_EVENT_INPUT, Some(cb), r.userdata()) };
+    assert!(e != null_mut());
+    unsafe { (*a).io_set_destroy.unwrap()(e, Some(io_destroy_cb)) };
+
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.count.get(), 0, "io event dispatched without data");
+
+    pipe.write_byte();
+    driver.set_readable(pipe.0, true);
+    assert!(driver.run_until(&|| r.count.get() > 0), "io event not dispatched");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.count.get(), 1, "disabled io event dispatched");
+
+    // Level triggered: the data is still there, so the event fires again once enabled.
+    unsafe { (*a).io_enable.unwrap()(e, PA_IO_EVENT_INPUT) };
+    assert!(driver.run_until(&|| r.count.get() > 1), "re-enabled io event not dispatched");
+
+    pipe.read_byte();
+    driver.set_readable(pipe.0, false);
+    unsafe { (*a).io_free.unwrap()(e) };
+    assert!(driver.run_until(&|| r.occurrences("io destroy") > 0), "io destroy callback not called");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.log(), vec!["io destroy"]);
+}
+
+/// An io event may free itself from its own callback.
+pub fn io_free_in_callback<D: Driver>(mut driver: D) {
+    extern "C" fn cb(a: *mut pa_mainloop_api, e: *mut pa_io_event, _fd: libc::c_int, _events: pa_io_event_flags_t, userdata: *mut libc::c_void) {
+        unsafe { recorder(userdata) }.push("io");
+        unsafe { (*a).io_free.unwrap()(e) };
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let pipe = Pipe::new();
+    let a = api.get_api();
+    let e = unsafe { (*a).io_new.unwrap()(a, pipe.0, PA_IO_EVENT_INPUT, Some(cb), r.userdata()) };
+    unsafe { (*a).io_set_destroy.unwrap()(e, Some(io_destroy_cb)) };
+
+    pipe.write_byte();
+    driver.set_readable(pipe.0, true);
+    assert!(driver.run_until(&|| r.occurrences("io destroy") > 0), "io destroy callback not called");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.log(), vec!["io", "io destroy"]);
+}
+
+/// Time events fire once, and may be restarted from their own callback.
+pub fn time_restart_in_callback<D: Driver>(mut driver: D) {
+    extern "C" fn cb(a: *mut pa_mainloop_api, e: *mut pa_time_event, _tv: *const libc::timeval, userdata: *mut libc::c_void) {
+        let r = unsafe { recorder(userdata) };
+        r.push("time");
+        r.count.set(r.count.get() + 1);
+        if r.count.get() < 3 {
+            let tv = timeval_after(Duration::from_millis(10));
+            unsafe { (*a).time_restart.unwrap()(e, &tv) };
+        }
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let a = api.get_api();
+    let tv = timeval_after(Duration::from_millis(10));
+    let e = unsafe { (*a).time_new.unwrap()(a, &tv, Some(cb), r.userdata()) };
+    unsafe { (*a).time_set_destroy.unwrap()(e, Some(time_destroy_cb)) };
+
+    assert!(driver.run_until(&|| r.count.get() >= 3), "time event not restarted");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.count.get(), 3, "time event fired without restart");
+
+    unsafe { (*a).time_free.unwrap()(e) };
+    assert!(driver.run_until(&|| r.occurrences("time destroy") > 0), "time destroy callback not called");
+    assert_eq!(r.log(), vec!["time", "time", "time", "time destroy"]);
+}
+
+/// A time event may free itself from its own callback.
+pub fn time_free_in_callback<D: Driver>(mut driver: D) {
+    extern "C" fn cb(a: *mut pa_mainloop_api, e: *mut pa_time_event, _tv: *const libc::timeval, userdata: *mut libc::c_void) {
+        unsafe { recorder(userdata) }.push("time");
+        unsafe { (*a).time_free.unwrap()(e) };
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let a = api.get_api();
+    let tv = timeval_after(Duration::from_millis(10));
+    let e = unsafe { (*a).time_new.unwrap()(a, &tv, Some(cb), r.userdata()) };
+    unsafe { (*a).time_set_destroy.unwrap()(e, Some(time_destroy_cb)) };
+
+    assert!(driver.run_until(&|| r.occurrences("time destroy") > 0), "time destroy callback not called");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.log(), vec!["time", "time destroy"]);
+}
+
+/// Freeing a pending time event outside of any callback cancels it.
+pub fn time_free_pending<D: Driver>(mut driver: D) {
+    extern "C" fn cb(_a: *mut pa_mainloop_api, _e: *mut pa_time_event, _tv: *const libc::timeval, userdata: *mut libc::c_void) {
+        unsafe { recorder(userdata) }.push("time");
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let a = api.get_api();
+    let tv = timeval_after(Duration::from_millis(20));
+    let e = unsafe { (*a).time_new.unwrap()(a, &tv, Some(cb), r.userdata()) };
+    unsafe { (*a).time_set_destroy.unwrap()(e, Some(time_destroy_cb)) };
+    unsafe { (*a).time_free.unwrap()(e) };
+
+    assert!(driver.run_until(&|| r.occurrences("time destroy") > 0), "time destroy callback not called");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.log(), vec!["time destroy"]);
+}
+
+/// A time event created without a time never fires.
+pub fn time_disabled<D: Driver>(mut driver: D) {
+    extern "C" fn cb(_a: *mut pa_mainloop_api, _e: *mut pa_time_event, _tv: *const libc::timeval, userdata: *mut libc::c_void) {
+        unsafe { recorder(userdata) }.push("time");
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let a = api.get_api();
+    let tv = timeval_after(Duration::from_millis(10));
+    let e = unsafe { (*a).time_new.unwrap()(a, &tv, Some(cb), r.userdata()) };
+    unsafe { (*a).time_restart.unwrap()(e, null_mut()) };
+
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.log(), Vec::<&str>::new());
+    unsafe { (*a).time_free.unwrap()(e) };
+    driver.run_for(Duration::from_millis(10));
+}
+
+/// A deferred event may enable another deferred event from its callback.
+pub fn defer_enable_in_defer<D: Driver>(mut driver: D) {
+    extern "C" fn first_cb(a: *mut pa_mainloop_api, e: *mut pa_defer_event, userdata: *mut libc::c_void) {
+        let r = unsafe { recorder(userdata) };
+        r.push("first");
+        unsafe {
+            (*a).defer_enable.unwrap()(e, 0);
+            (*a).defer_enable.unwrap()(r.other_defer.get(), 1);
+        }
+    }
+
+    extern "C" fn second_cb(a: *mut pa_mainloop_api, e: *mut pa_defer_event, userdata: *mut libc::c_void) {
+        unsafe { recorder(userdata) }.push("second");
+        unsafe { (*a).defer_enable.unwrap()(e, 0) };
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let a = api.get_api();
+    let second = unsafe { (*a).defer_new.unwrap()(a, Some(second_cb), r.userdata()) };
+    unsafe { (*a).defer_enable.unwrap()(second, 0) };
+    r.other_defer.set(second);
+    let first = unsafe { (*a).defer_new.unwrap()(a, Some(first_cb), r.userdata()) };
+
+    assert!(driver.run_until(&|| r.occurrences("second") > 0), "enabled deferred event not dispatched");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.log(), vec!["first", "second"]);
+
+    unsafe {
+        (*a).defer_free.unwrap()(first);
+        (*a).defer_free.unwrap()(second);
+    }
+    driver.run_for(Duration::from_millis(10));
+}
+
+/// A deferred event may free itself from its own callback. The destroy callback is
+/// called once, after the last callback.
+pub fn defer_free_in_callback<D: Driver>(mut driver: D) {
+    extern "C" fn cb(a: *mut pa_mainloop_api, e: *mut pa_defer_event, userdata: *mut libc::c_void) {
+        unsafe { recorder(userdata) }.push("defer");
+        unsafe { (*a).defer_free.unwrap()(e) };
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let a = api.get_api();
+    let e = unsafe { (*a).defer_new.unwrap()(a, Some(cb), r.userdata()) };
+    unsafe { (*a).defer_set_destroy.unwrap()(e, Some(defer_destroy_cb)) };
+
+    assert!(driver.run_until(&|| r.occurrences("defer destroy") > 0), "defer destroy callback not called");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.log(), vec!["defer", "defer destroy"]);
+}
+
+/// Each destroy callback is called exactly once, after the event was freed.
+pub fn destroy_callbacks<D: Driver>(mut driver: D) {
+    extern "C" fn io_cb(_a: *mut pa_mainloop_api, _e: *mut pa_io_event, _fd: libc::c_int, _events: pa_io_event_flags_t, _userdata: *mut libc::c_void) {}
+    extern "C" fn time_cb(_a: *mut pa_mainloop_api, _e: *mut pa_time_event, _tv: *const libc::timeval, _userdata: *mut libc::c_void) {}
+    extern "C" fn defer_cb(a: *mut pa_mainloop_api, e: *mut pa_defer_event, _userdata: *mut libc::c_void) {
+        unsafe { (*a).defer_enable.unwrap()(e, 0) };
+    }
+
+    let api = driver.api();
+    let r = Recorder::new(&api);
+    let pipe = Pipe::new();
+    let a = api.get_api();
+    let tv = timeval_after(Duration::from_secs(60));
+    unsafe {
+        r.io.set((*a).io_new.unwrap()(a, pipe.0, PA_IO_EVENT_INPUT, Some(io_cb), r.userdata()));
+        (*a).io_set_destroy.unwrap()(r.io.get(), Some(io_destroy_cb));
+        r.time.set((*a).time_new.unwrap()(a, &tv, Some(time_cb), r.userdata()));
+        (*a).time_set_destroy.unwrap()(r.time.get(), Some(time_destroy_cb));
+        r.defer.set((*a).defer_new.unwrap()(a, Some(defer_cb), r.userdata()));
+        (*a).defer_set_destroy.unwrap()(r.defer.get(), Some(defer_destroy_cb));
+    }
+
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.log(), Vec::<&str>::new(), "destroy callback called before free");
+
+    unsafe {
+        (*a).defer_free.unwrap()(r.defer.get());
+        (*a).time_free.unwrap()(r.time.get());
+        (*a).io_free.unwrap()(r.io.get());
+    }
+    assert!(driver.run_until(&|| r.log().len() >= 3), "destroy callbacks not called");
+    driver.run_for(Duration::from_millis(50));
+    assert_eq!(r.occurrences("io destroy"), 1);
+    assert_eq!(r.occurrences("time destroy"), 1);
+    assert_eq!(r.occurrences("defer destroy"), 1);
+}
+
+/// Runs all conformance tests, creating a fresh driver for each.
+pub fn run_all<D: Driver, F: Fn() -> D>(make: F) {
+    io_enable_disable(make());
+    io_free_in_callback(make());
+    time_restart_in_callback(make());
+    time_free_in_callback(make());
+    time_free_pending(make());
+    time_disabled(make());
+    defer_enable_in_defer(make());
+    defer_free_in_callback(make());
+    destroy_callbacks(make());
+}
@@ -0,0 +1,438 @@
+extern crate futures;
+extern crate libc;
+extern crate libpulse_sys;
+extern crate pulseaudio;
+#[cfg(feature = "tokio")]
+extern crate tokio;
+extern crate tokio_core;
+
+mod conformance;
+
+use pulseaudio::standard_mainloop::StandardMainLoop;
+use pulseaudio::tokio_mainloop_api::PaMainLoopApiTokio;
+use pulseaudio::virtual_mainloop::VirtualMainLoop;
+use std::cmp;
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+use tokio_core::reactor::Core;
+
+use conformance::Driver;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+const STEP: Duration = Duration::from_millis(10);
+
+/// Drives a mainloop in steps of at most `STEP` with the given step function.
+fn run_until<F: FnMut(Duration)>(done: &Fn() -> bool, mut step: F) -> bool {
+    let start = Instant::now();
+    while !done() {
+        if start.elapsed() > TIMEOUT {
+            return false;
+        }
+        step(STEP);
+    }
+    true
+}
+
+fn run_for<F: FnMut(Duration)>(duration: Duration, mut step: F) {
+    let start = Instant::now();
+    while let Some(remaining) = duration.checked_sub(start.elapsed()) {
+        step(cmp::min(remaining, STEP));
+    }
+}
+
+struct StandardDriver(StandardMainLoop);
+
+impl StandardDriver {
+    fn new() -> StandardDriver {
+        StandardDriver(StandardMainLoop::new())
+    }
+
+    fn step(mainloop: &StandardMainLoop, timeout: Duration) {
+        mainloop.prepare(Some(timeout)).unwrap();
+        mainloop.poll().unwrap();
+        mainloop.dispatch().unwrap();
+    }
+}
+
+impl Driver for StandardDriver {
+    type Api = StandardMainLoop;
+
+    fn api(&self) -> StandardMainLoop {
+        self.0.clone()
+    }
+
+    fn run_until(&mut self, done: &Fn() -> bool) -> bool {
+        let mainloop = &self.0;
+        run_until(done, |t| Self::step(mainloop, t))
+    }
+
+    fn run_for(&mut self, duration: Duration) {
+        let mainloop = &self.0;
+        run_for(duration, |t| Self::step(mainloop, t))
+    }
+}
+
+struct TokioDriver(Core, PaMainLoopApiTokio);
+
+impl TokioDriver {
+    fn new() -> TokioDriver {
+        let core = Core::new().unwrap();
+        let api = PaMainLoopApiTokio::new(&core.handle());
+        TokioDriver(core, api)
+    }
+
+    fn with_deferred_budget(rounds: u32) -> TokioDriver {
+        let core = Core::new().unwrap();
+        let api = PaMainLoopApiTokio::with_deferred_budget(&core.handle(), rounds);
+        TokioDriver(core, api)
+    }
+}
+
+impl Driver for TokioDriver {
+    type Api = PaMainLoopApiTokio;
+
+    fn api(&self) -> PaMainLoopApiTokio {
+        self.1.clone()
+    }
+
+    fn run_until(&mut self, done: &Fn() -> bool) -> bool {
+        let core = &mut self.0;
+        run_until(done, |t| core.turn(Some(t)))
+    }
+
+    fn run_for(&mut self, duration: Duration) {
+        let core = &mut self.0;
+        run_for(duration, |t| core.turn(Some(t)))
+    }
+}
+
+#[cfg(feature = "tokio")]
+struct Tokio1Driver {
+    runtime: tokio::runtime::Runtime,
+    local: tokio::task::LocalSet,
+    api: pulseaudio::tokio1_mainloop_api::PaMainLoopApiTokio1,
+}
+
+#[cfg(feature = "tokio")]
+impl Tokio1Driver {
+    fn new() -> Tokio1Driver {
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let local = tokio::task::LocalSet::new();
+        let api = {
+            let _runtime = runtime.enter();
+            let _local = local.enter();
+            pulseaudio::tokio1_mainloop_api::PaMainLoopApiTokio1::new()
+        };
+        Tokio1Driver { runtime, local, api }
+    }
+
+    fn step(runtime: &tokio::runtime::Runtime, local: &tokio::task::LocalSet, timeout: Duration) {
+        let sleep = {
+            let _runtime = runtime.enter();
+            tokio::time::sleep(timeout)
+        };
+        local.block_on(runtime, sleep);
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl Driver for Tokio1Driver {
+    type Api = pulseaudio::tokio1_mainloop_api::PaMainLoopApiTokio1;
+
+    fn api(&self) -> Self::Api {
+        self.api.clone()
+    }
+
+    fn run_until(&mut self, done: &Fn() -> bool) -> bool {
+        let (runtime, local) = (&self.runtime, &self.local);
+        run_until(done, |t| Self::step(runtime, local, t))
+    }
+
+    fn run_for(&mut self, duration: Duration) {
+        let (runtime, local) = (&self.runtime, &self.local);
+        run_for(duration, |t| Self::step(runtime, local, t))
+    }
+}
+
+struct VirtualDriver(VirtualMainLoop);
+
+impl VirtualDriver {
+    fn new() -> VirtualDriver {
+        VirtualDriver(VirtualMainLoop::new())
+    }
+
+    /// Iterates until idle, then advances the clock to the next deadline up to `limit`.
+    /// Returns false if there is nothing left to do before `limit`.
+    fn step(&self, limit: Option<Duration>) -> bool {
+        self.0.run_until_idle(1000);
+        match self.0.next_deadline() {
+            Some(deadline) if limit.map_or(true, |l| deadline <= l) => {
+                let now = self.0.now();
+                self.0.advance(deadline - cmp::min(deadline, now));
+                true
+            },
+            _ => false,
+        }
+    }
+}
+
+impl Driver for VirtualDriver {
+    type Api = VirtualMainLoop;
+
+    fn api(&self) -> VirtualMainLoop {
+        self.0.clone()
+    }
+
+    fn run_until(&mut self, done: &Fn() -> bool) -> bool {
+        for _ in 0..1000 {
+            if done() {
+                return true;
+            }
+            if !self.step(None) {
+                break;
+            }
+        }
+        self.0.run_until_idle(1000);
+        done()
+    }
+
+    fn run_for(&mut self, duration: Duration) {
+        let limit = self.0.now() + duration;
+        for _ in 0..1000 {
+            if !self.step(Some(limit)) {
+                break;
+            }
+        }
+        let now = self.0.now();
+        self.0.advance(limit - cmp::min(limit, now));
+        self.0.run_until_idle(1000);
+    }
+
+    fn set_readable(&mut self, fd: RawFd, readable: bool) {
+        let events = if readable { libpulse_sys::PA_IO_EVENT_INPUT } else { libpulse_sys::PA_IO_EVENT_NULL };
+        self.0.set_io_ready(fd, events);
+    }
+}
+
+macro_rules! conformance_tests {
+    ($name:ident, $driver:expr) => {
+        mod $name {
+            use super::*;
+
+            #[test]
+            fn io_enable_disable() {
+                conformance::io_enable_disable($driver);
+            }
+
+            #[test]
+            fn io_free_in_callback() {
+                conformance::io_free_in_callback($driver);
+            }
+
+            #[test]
+            fn time_restart_in_callback() {
+                conformance::time_restart_in_callback($driver);
+            }
+
+            #[test]
+            fn time_free_in_callback() {
+                conformance::time_free_in_callback($driver);
+            }
+
+            #[test]
+            fn time_free_pending() {
+                conformance::time_free_pending($driver);
+            }
+
+            #[test]
+            fn time_disabled() {
+                conformance::time_disabled($driver);
+            }
+
+            #[test]
+            fn defer_enable_in_defer() {
+                conformance::defer_enable_in_defer($driver);
+            }
+
+            #[test]
+            fn defer_free_in_callback() {
+                conformance::defer_free_in_callback($driver);
+            }
+
+            #[test]
+            fn destroy_callbacks() {
+                conformance::destroy_callbacks($driver);
+            }
+        }
+    }
+}
+
+conformance_tests!(standard, StandardDriver::new());
+conformance_tests!(tokio_core_api, TokioDriver::new());
+#[cfg(feature = "tokio")]
+conformance_tests!(tokio1, Tokio1Driver::new());
+conformance_tests!(virtual_clock, VirtualDriver::new());
+
+/// Tests of features specific to `PaMainLoopApiTokio`.
+mod tokio_core_specific {
+    use libc;
+    use libpulse_sys::*;
+    use pulseaudio::mainloop_api::PaMainLoopApi;
+    use std::cell::Cell;
+    use std::ptr::null_mut;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use super::*;
+
+    /// Number of callbacks per event kind.
+    #[derive(Default)]
+    struct Counts {
+        io: Cell<u32>,
+        io_flags: Cell<pa_io_event_flags_t>,
+        time: Cell<u32>,
+        defer: Cell<u32>,
+    }
+
+    impl Counts {
+        fn userdata(&self) -> *mut libc::c_void {
+            self as *const Counts as *mut libc::c_void
+        }
+    }
+
+    unsafe fn counts<'a>(userdata: *mut libc::c_void) -> &'a Counts {
+        assert!(userdata != null_mut());
+        &*(userdata as *const Counts)
+    }
+
+    fn increment(c: &Cell<u32>) {
+        c.set(c.get() + 1);
+    }
+
+    fn timeval_after(delay: Duration) -> libc::timeval {
+        let t = SystemTime::now().duration_since(UNIX_EPOCH).unwrap() + delay;
+        libc::timeval { tv_sec: t.as_secs() as libc::time_t, tv_usec: t.subsec_micros() as libc::suseconds_t }
+    }
+
+    #[cfg(feature = "stats")]
+    extern "C" fn io_cb(a: *mut pa_mainloop_api, e: *mut pa_io_event, _fd: libc::c_int, _events: pa_io_event_flags_t, userdata: *mut libc::c_void) {
+        increment(&unsafe { counts(userdata) }.io);
+        unsafe { (*a).io_enable.unwrap()(e, PA_IO_EVENT_NULL) };
+    }
+
+    extern "C" fn io_error_cb(a: *mut pa_mainloop_api, e: *mut pa_io_event, _fd: libc::c_int, events: pa_io_event_flags_t, userdata: *mut libc::c_void) {
+        let c = unsafe { counts(userdata) };
+        increment(&c.io);
+        c.io_flags.set(events);
+        unsafe { (*a).io_enable.unwrap()(e, PA_IO_EVENT_NULL) };
+    }
+
+    extern "C" fn time_cb(_a: *mut pa_mainloop_api, _e: *mut pa_time_event, _tv: *const libc::timeval, userdata: *mut libc::c_void) {
+        increment(&unsafe { counts(userdata) }.time);
+    }
+
+    #[cfg(feature = "stats")]
+    extern "C" fn defer_once_cb(a: *mut pa_mainloop_api, e: *mut pa_defer_event, userdata: *mut libc::c_void) {
+        increment(&unsafe { counts(userdata) }.defer);
+        unsafe { (*a).defer_enable.unwrap()(e, 0) };
+    }
+
+    extern "C" fn defer_always_cb(_a: *mut pa_mainloop_api, _e: *mut pa_defer_event, userdata: *mut libc::c_void) {
+        increment(&unsafe { counts(userdata) }.defer);
+    }
+
+    /// With a deferred budget, a deferred event that is never disabled does not keep a
+    /// time event from firing.
+    #[test]
+    fn deferred_budget() {
+        let mut driver = TokioDriver::with_deferred_budget(4);
+        let api = driver.api();
+        let c = Counts::default();
+        let a = api.get_api();
+        let defer = unsafe { (*a).defer_new.unwrap()(a, Some(defer_always_cb), c.userdata()) };
+        let tv = timeval_after(Duration::from_millis(10));
+        let time = unsafe { (*a).time_new.unwrap()(a, &tv, Some(time_cb), c.userdata()) };
+
+        assert!(driver.run_until(&|| c.time.get() > 0), "time event starved by deferred event");
+        assert!(c.defer.get() > 0, "deferred event not dispatched");
+
+        unsafe {
+            (*a).defer_free.unwrap()(defer);
+            (*a).time_free.unwrap()(time);
+        }
+        driver.run_for(Duration::from_millis(10));
+    }
+
+    /// A file descriptor that cannot be watched is reported to libpulse as a hangup and
+    /// error event, and to the stream of io errors.
+    #[test]
+    fn io_error() {
+        use futures::Stream;
+
+        let mut driver = TokioDriver::new();
+        let api = driver.api();
+        let errors = api.io_errors();
+        let c = Counts::default();
+        let a = api.get_api();
+        // Above any file descriptor limit, so it is never open.
+        let fd = 1 << 30;
+        let e = unsafe { (*a).io_new.unwrap()(a, fd, PA_IO_EVENT_INPUT, Some(io_error_cb), c.userdata()) };
+
+        assert!(driver.run_until(&|| c.io.get() > 0), "error event not dispatched");
+        assert_eq!(c.io_flags.get(), PA_IO_EVENT_HANGUP | PA_IO_EVENT_ERROR);
+        let error = match driver.0.run(errors.into_future()) {
+            Ok((Some(error), _)) => error,
+            _ => panic!("io error not reported"),
+        };
+        assert_eq!(error.fd(), fd);
+        assert_eq!(error.error().raw_os_error(), Some(libc::EBADF));
+
+        unsafe { (*a).io_free.unwrap()(e) };
+        driver.run_for(Duration::from_millis(10));
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        assert_eq!(unsafe { libc::write(fds[1], b"x".as_ptr() as *const libc::c_void, 1) }, 1);
+
+        let mut driver = TokioDriver::new();
+        let api = driver.api();
+        let c = Counts::default();
+        let a = api.get_api();
+        let tv = timeval_after(Duration::from_millis(10));
+        let (io, time, defer) = unsafe {
+            ((*a).io_new.unwrap()(a, fds[0], PA_IO_EVENT_INPUT, Some(io_cb), c.userdata()),
+             (*a).time_new.unwrap()(a, &tv, Some(time_cb), c.userdata()),
+             (*a).defer_new.unwrap()(a, Some(defer_once_cb), c.userdata()))
+        };
+
+        let stats = api.stats();
+        assert_eq!((stats.io_events, stats.time_events, stats.defer_events), (1, 1, 1));
+        assert_eq!((stats.io_dispatched, stats.time_dispatched, stats.defer_dispatched), (0, 0, 0));
+
+        assert!(driver.run_until(&|| c.io.get() > 0 && c.time.get() > 0 && c.defer.get() > 0), "events not dispatched");
+        driver.run_for(Duration::from_millis(50));
+        let stats = api.stats();
+        assert_eq!((stats.io_dispatched, stats.time_dispatched, stats.defer_dispatched), (1, 1, 1));
+        assert!(stats.deferred_polls >= 1);
+        // One round dispatches the event, the next finds nothing enabled.
+        assert_eq!(stats.max_deferred_rounds, 2);
+        assert!(stats.deferred_rounds >= stats.deferred_polls);
+
+        unsafe {
+            (*a).io_free.unwrap()(io);
+            (*a).time_free.unwrap()(time);
+            (*a).defer_free.unwrap()(defer);
+        }
+        driver.run_for(Duration::from_millis(50));
+        let stats = api.stats();
+        assert_eq!((stats.io_events, stats.time_events, stats.defer_events), (0, 0, 0));
+
+        unsafe {
+            libc::close(fds[0]);
+            libc::close(fds[1]);
+        }
+    }
+}
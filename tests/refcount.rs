@@ -0,0 +1,43 @@
+extern crate libpulse_sys;
+extern crate pulseaudio;
+
+use libpulse_sys::*;
+use pulseaudio::context::PaContext;
+use pulseaudio::proplist::Proplist;
+use pulseaudio::refcount::RefCounted;
+use pulseaudio::standard_mainloop::StandardMainLoop;
+use std::ffi::CStr;
+
+#[test]
+fn context_reference_round_trip() {
+    let mainloop = StandardMainLoop::new();
+    let context = PaContext::new(&mainloop, CStr::from_bytes_with_nul(b"refcount test\0").unwrap());
+    let ptr = context.raw().get();
+
+    let reference = unsafe { RefCounted::new_ref(ptr) };
+    let raw = reference.clone().into_raw();
+    assert_eq!(raw, ptr);
+    // Adopts the reference transferred by `into_raw`.
+    let adopted = unsafe { RefCounted::new(raw) };
+    assert_eq!(adopted.get(), ptr);
+    drop(adopted);
+    drop(reference);
+
+    // The context still holds its own reference.
+    assert_eq!(unsafe { pa_context_get_state(context.raw().get()) }, PA_CONTEXT_UNCONNECTED);
+}
+
+#[test]
+fn proplist_round_trip() {
+    let key = CStr::from_bytes_with_nul(b"media.role\0").unwrap();
+    let value = CStr::from_bytes_with_nul(b"music\0").unwrap();
+    let mut proplist = Proplist::new();
+    assert!(proplist.set(key, value));
+
+    let raw = proplist.into_raw();
+    assert_eq!(unsafe { pa_proplist_size(raw) }, 1);
+    let proplist = unsafe { Proplist::from_raw(raw) };
+    assert_eq!(proplist.get(key), Some(value));
+    assert_eq!(proplist.into_raw(), raw);
+    drop(unsafe { Proplist::from_raw(raw) });
+}
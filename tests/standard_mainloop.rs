@@ -0,0 +1,10 @@
+extern crate pulseaudio;
+
+use pulseaudio::standard_mainloop::StandardMainLoop;
+
+#[test]
+fn run_returns_quit_value() {
+    let mainloop = StandardMainLoop::new();
+    mainloop.quit(42);
+    assert_eq!(mainloop.run(), Ok(42));
+}
@@ -0,0 +1,40 @@
+//! Panics in callbacks dispatched by `PaMainLoopApiTokio1` must reach the user's
+//! task without killing the internal event tasks.
+#![cfg(feature = "tokio")]
+extern crate pulseaudio;
+extern crate tokio;
+
+use pulseaudio::context::PaContext;
+use pulseaudio::tokio1_mainloop_api::PaMainLoopApiTokio1;
+use std::ffi::CStr;
+use std::panic::{self, AssertUnwindSafe};
+use std::time::Duration;
+
+#[test]
+fn callback_panic_reaches_user_task() {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let local = tokio::task::LocalSet::new();
+    let handle = {
+        let _runtime = runtime.enter();
+        let _local = local.enter();
+        let api = PaMainLoopApiTokio1::new();
+        let context = PaContext::new(&api, CStr::from_bytes_with_nul(b"panic test\0").unwrap());
+        context.handle().unwrap()
+    };
+
+    // Dispatched together with the panicking command, and still pending afterwards.
+    let pending = handle.spawn(|_| tokio::time::sleep(Duration::from_millis(50)));
+
+    // The command runs in a libpulse io callback on the internal io task.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        local.block_on(&runtime, handle.run(|_| -> () { panic!("callback panic") }))
+    }));
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"callback panic"));
+
+    // The panic only cancels its own command.
+    assert_eq!(local.block_on(&runtime, pending), Ok(()));
+
+    // The io event is still dispatched.
+    assert_eq!(local.block_on(&runtime, handle.run(|_| 42)), Ok(42));
+}
@@ -0,0 +1,28 @@
+//! Panics in callbacks dispatched by `PaMainLoopApiTokio` must reach the user's
+//! task without killing the internal event tasks.
+extern crate pulseaudio;
+extern crate tokio_core;
+
+use pulseaudio::context::PaContext;
+use pulseaudio::tokio_mainloop_api::PaMainLoopApiTokio;
+use std::ffi::CStr;
+use std::panic::{self, AssertUnwindSafe};
+use tokio_core::reactor::Core;
+
+#[test]
+fn callback_panic_reaches_user_task() {
+    let mut core = Core::new().unwrap();
+    let api = PaMainLoopApiTokio::new(&core.handle());
+    let context = PaContext::new(&api, CStr::from_bytes_with_nul(b"panic test\0").unwrap());
+    let handle = context.handle().unwrap();
+
+    // The command runs in a libpulse io callback on the internal io task.
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        core.run(handle.run(|_| -> () { panic!("callback panic") }))
+    }));
+    let payload = result.unwrap_err();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"callback panic"));
+
+    // The io event is still dispatched.
+    assert_eq!(core.run(handle.run(|_| 42)), Ok(42));
+}